@@ -1,12 +1,46 @@
-use syn::{Block, Ident, Item, ItemEnum, ItemStruct, Pat, Type, Attribute};
+use proc_macro2::Span;
+use std::rc::Rc;
+use syn::{Block, Ident, Item, ItemEnum, ItemStruct, LitInt, Pat, Type, Attribute};
 
 #[derive(Debug, Copy, Clone)]
 pub enum Associativity {
     Left,
     Right,
     None,
+    Precedence, //No associativity at all, from %precedence
 }
 
+//Modifier of a symbol in the RHS of a rule: `X`, `X?`, `X*` or `X+`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Quantifier {
+    One,
+    Optional,
+    ZeroOrMore,
+    OneOrMore,
+}
+
+//What is written in the RHS of a rule: symbols `A|B|C`, a group of alternatives `(a B | C)`,
+//a parameterized non-terminal `list(A, B)` or a mid-rule action `{ code }(alias: Type)`
+#[derive(Debug, Clone)]
+pub enum RhsTerm {
+    Symbol(Vec<Ident>),
+    Group(Span, Vec<Vec<RhsSymbol>>),
+    Apply(Ident, Vec<RhsSymbol>),
+    Action(Block, Option<Box<Type>>),
+}
+
+//A symbol in the RHS of a rule with its modifiers
+#[derive(Debug, Clone)]
+pub struct RhsSymbol {
+    pub term: RhsTerm,
+    pub quantifier: Quantifier,
+    pub separator: Option<(Vec<Ident>, bool)>, //`% Sep`, true if it can be trailing: `% Sep?`
+    pub alias: Option<Pat>,
+}
+
+//Where a declaration is, if it comes from an included grammar file: `path:line:column`
+pub type Location = Option<Rc<str>>;
+
 #[derive(Debug)]
 pub enum Decl {
     Module(Ident),
@@ -14,23 +48,38 @@ pub enum Decl {
     SyntaxError(Block),
     ParseFail(Block),
     StackOverflow(Block),
-    Type(Vec<Attribute>, Ident, Option<Type>),
+    Destructor(Ident, Option<Pat>, Block),
+    TokenDestructor(Block),
+    Type(Vec<Attribute>, Ident, Vec<Ident>, Option<Type>),
     Assoc(Associativity, Vec<Ident>),
     DefaultType(Type),
     ExtraArgument(Type),
     Error(Type),
-    StartSymbol(Ident),
+    StartSymbol(Ident, Vec<Ident>),
+    Inline(Vec<Ident>),
     Fallback(Ident, Vec<Ident>),
     Wildcard(Ident),
     TokenClass(Ident, Vec<Ident>),
     Token(ItemEnum),
     ExtraToken(Type),
-    StackSize(usize, Option<Type>),
+    StackSize(Option<usize>, Option<Type>), //None for `auto`
     Parser(ItemStruct),
     Verbose,
+    Algorithm(Ident),
+    Tables(Ident),
+    Glr,
+    Merge(Ident, Pat, Pat, Block),
+    Deny(Vec<Ident>),
+    Allow(Vec<Ident>),
+    Strict,
+    Terminal(Vec<Ident>),
+    NonTerminal(Vec<Ident>),
+    Expect(LitInt),
+    ExpectRR(LitInt),
     Rule {
         lhs: Ident,
-        rhs: Vec<(Vec<Ident>, bool, Option<Pat>)>,
+        params: Vec<Ident>,
+        rhs: Vec<RhsSymbol>,
         action: Option<Block>,
         prec: Option<Ident>,
     },
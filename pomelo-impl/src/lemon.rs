@@ -0,0 +1,605 @@
+//Front end for grammar files written for Lemon_C, read with the `%include_lemon` directive.
+//
+//Only the grammar is imported: the rules, the precedences and the directives about tokens.
+//The C code and the C types cannot be used, so they are skipped: the Rust types go in the
+//`pomelo!` macro as usual and the Rust actions of the rules in a side file written in the
+//syntax of pomelo. Rules without a Rust action are left as stubs.
+
+use crate::decl::*;
+use proc_macro2::Span;
+use std::collections::{BTreeMap, HashSet};
+use syn::parse::{Error, Result};
+use syn::{Block, Ident, LitStr};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Directive(String),
+    Code(String),
+    Str,
+    Assign,
+    Punct(char),
+}
+
+//Line and column of a token in the file
+#[derive(Debug, Copy, Clone)]
+struct Loc(usize, usize);
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    loc: Loc,
+}
+
+impl<'a> Lexer<'a> {
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.loc = Loc(self.loc.0 + 1, 1);
+        } else {
+            self.loc.1 += 1;
+        }
+        Some(c)
+    }
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().cloned()
+    }
+    fn next_if(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+    //Skips a comment, the first `/` is already read. Returns false if it is not a comment.
+    fn skip_comment(&mut self) -> bool {
+        if self.next_if('/') {
+            while let Some(c) = self.next() {
+                if c == '\n' {
+                    break;
+                }
+            }
+        } else if self.next_if('*') {
+            let mut prev = ' ';
+            while let Some(c) = self.next() {
+                if prev == '*' && c == '/' {
+                    break;
+                }
+                prev = c;
+            }
+        } else {
+            return false;
+        }
+        true
+    }
+    //Skips a C string or character literal, the opening quote is already read
+    fn skip_quoted(&mut self, quote: char) -> bool {
+        while let Some(c) = self.next() {
+            if c == '\\' {
+                self.next();
+            } else if c == quote {
+                return true;
+            }
+        }
+        false
+    }
+    //The C code inside braces, the first `{` is already read
+    fn code(&mut self) -> Option<String> {
+        let mut code = String::new();
+        let mut level = 1;
+        while let Some(c) = self.next() {
+            match c {
+                '{' => level += 1,
+                '}' => {
+                    level -= 1;
+                    if level == 0 {
+                        return Some(code);
+                    }
+                }
+                '"' | '\'' if !self.skip_quoted(c) => return None,
+                '/' if self.skip_comment() => continue,
+                _ => (),
+            }
+            code.push(c);
+        }
+        None
+    }
+}
+
+fn tokenize(text: &str) -> std::result::Result<Vec<(Tok, Loc)>, (Loc, &'static str)> {
+    let mut lex = Lexer {
+        chars: text.chars().peekable(),
+        loc: Loc(1, 1),
+    };
+    let mut toks = Vec::new();
+    loop {
+        let loc = lex.loc;
+        let c = match lex.next() {
+            None => break,
+            Some(c) => c,
+        };
+        let tok = match c {
+            c if c.is_whitespace() => continue,
+            '/' if lex.skip_comment() => continue,
+            '{' => match lex.code() {
+                Some(code) => Tok::Code(code),
+                None => return Err((loc, "Unterminated code block")),
+            },
+            '"' => {
+                if !lex.skip_quoted('"') {
+                    return Err((loc, "Unterminated string"));
+                }
+                Tok::Str
+            }
+            ':' if lex.next_if(':') => {
+                if !lex.next_if('=') {
+                    return Err((loc, "`::=` expected"));
+                }
+                Tok::Assign
+            }
+            '%' if lex.peek().is_some_and(|c| c.is_ascii_alphabetic()) => {
+                let mut name = String::new();
+                while let Some(c) = lex
+                    .peek()
+                    .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+                {
+                    name.push(c);
+                    lex.next();
+                }
+                Tok::Directive(name)
+            }
+            c if c.is_ascii_alphanumeric() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some(c) = lex
+                    .peek()
+                    .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+                {
+                    name.push(c);
+                    lex.next();
+                }
+                Tok::Ident(name)
+            }
+            '.' | '(' | ')' | '[' | ']' | '|' | '/' => Tok::Punct(c),
+            _ => return Err((loc, "Unexpected character")),
+        };
+        toks.push((tok, loc));
+    }
+    Ok(toks)
+}
+
+//A rule as written in the Lemon grammar
+struct LemonRule {
+    loc: Loc,
+    lhs: Ident,
+    rhs: Vec<Vec<Ident>>,
+    has_code: bool,
+    prec: Option<Ident>,
+}
+
+struct LemonParser {
+    path: String,
+    span: Span,
+    toks: Vec<(Tok, Loc)>,
+    pos: usize,
+    //Stack of `%ifdef` blocks: true if its contents are used
+    ifdefs: Vec<bool>,
+    //Lemon token classes are lowercase, pomelo ones are tokens, so they are renamed
+    classes: BTreeMap<String, String>,
+    //All the names in the grammar, to check that renamed classes do not clash
+    names: HashSet<String>,
+    decls: Vec<(Decl, Location)>,
+    rules: Vec<LemonRule>,
+    //The directives with C code, reported all together at the end
+    c_errors: Vec<Error>,
+}
+
+impl LemonParser {
+    fn location(&self, loc: Loc) -> Location {
+        let Loc(line, col) = loc;
+        Some(format!("{}:{}:{}", self.path, line, col).into())
+    }
+    fn push_decl(&mut self, loc: Loc, decl: Decl) {
+        let location = self.location(loc);
+        self.decls.push((decl, location));
+    }
+    fn error<T>(&self, loc: Loc, msg: &str) -> Result<T> {
+        Err(self.make_error(loc, msg))
+    }
+    fn make_error(&self, loc: Loc, msg: &str) -> Error {
+        let Loc(line, col) = loc;
+        Error::new(self.span, format!("{}:{}:{}: {}", self.path, line, col, msg))
+    }
+    //Location of the current token, or of the last one if at the end
+    fn loc(&self) -> Loc {
+        match self.toks.get(self.pos).or_else(|| self.toks.last()) {
+            Some((_, loc)) => *loc,
+            None => Loc(1, 1),
+        }
+    }
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos).map(|(tok, _)| tok)
+    }
+    fn next(&mut self) -> Option<Tok> {
+        let tok = self.peek().cloned();
+        self.pos += 1;
+        tok
+    }
+    fn next_if(&mut self, tok: Tok) -> bool {
+        if self.peek() == Some(&tok) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+    fn ident_name(&mut self) -> Result<String> {
+        let loc = self.loc();
+        match self.next() {
+            Some(Tok::Ident(name)) => {
+                self.names.insert(name.clone());
+                Ok(self.classes.get(&name).cloned().unwrap_or(name))
+            }
+            _ => self.error(loc, "Identifier expected"),
+        }
+    }
+    fn ident(&mut self) -> Result<Ident> {
+        let loc = self.loc();
+        let name = self.ident_name()?;
+        self.make_ident(&name, loc)
+    }
+    fn make_ident(&self, name: &str, loc: Loc) -> Result<Ident> {
+        match syn::parse_str::<Ident>(name) {
+            Ok(mut id) => {
+                id.set_span(self.span);
+                Ok(id)
+            }
+            Err(_) => self.error(loc, "Symbol name is not a valid Rust identifier"),
+        }
+    }
+    //A list of identifiers ended with a period
+    fn ident_list(&mut self) -> Result<Vec<Ident>> {
+        let mut ids = Vec::new();
+        while !self.next_if(Tok::Punct('.')) {
+            ids.push(self.ident()?);
+        }
+        Ok(ids)
+    }
+    //Skips the argument of a directive that has no use in pomelo
+    fn skip_arg(&mut self) -> Result<()> {
+        let loc = self.loc();
+        match self.next() {
+            Some(Tok::Ident(_)) | Some(Tok::Code(_)) | Some(Tok::Str) => Ok(()),
+            _ => self.error(loc, "Argument expected"),
+        }
+    }
+
+    fn parse(&mut self) -> Result<()> {
+        while let Some(tok) = self.peek().cloned() {
+            let loc = self.loc();
+            match tok {
+                Tok::Directive(name) => {
+                    self.pos += 1;
+                    self.directive(&name, loc)?;
+                }
+                _ if self.ifdefs.contains(&false) => {
+                    self.pos += 1;
+                }
+                Tok::Ident(_) => self.rule()?,
+                _ => return self.error(loc, "Directive or rule expected"),
+            }
+        }
+        if !self.ifdefs.is_empty() {
+            return self.error(self.loc(), "Missing %endif");
+        }
+        let mut c_errors = std::mem::take(&mut self.c_errors).into_iter();
+        if let Some(mut error) = c_errors.next() {
+            for e in c_errors {
+                error.combine(e);
+            }
+            return Err(error);
+        }
+        if let Some(class) = self.classes.values().find(|c| self.names.contains(*c)) {
+            let msg = format!(
+                "Token class is renamed to `{}`, but it is already used",
+                class
+            );
+            return self.error(Loc(1, 1), &msg);
+        }
+        Ok(())
+    }
+
+    fn directive(&mut self, name: &str, loc: Loc) -> Result<()> {
+        //Conditional blocks, only POMELO is defined
+        match name {
+            "ifdef" | "ifndef" => {
+                let defined = self.ident_name()? == "POMELO";
+                self.ifdefs.push(defined == (name == "ifdef"));
+                return Ok(());
+            }
+            "else" => {
+                return match self.ifdefs.last_mut() {
+                    Some(active) => {
+                        *active = !*active;
+                        Ok(())
+                    }
+                    None => self.error(loc, "%else without %ifdef"),
+                };
+            }
+            "endif" => {
+                return match self.ifdefs.pop() {
+                    Some(_) => Ok(()),
+                    None => self.error(loc, "%endif without %ifdef"),
+                };
+            }
+            "if" | "elseif" => {
+                return self.error(loc, "Only %ifdef and %ifndef are supported");
+            }
+            _ => (),
+        }
+        if self.ifdefs.contains(&false) {
+            return Ok(());
+        }
+        match name {
+            "left" | "right" | "nonassoc" => {
+                let assoc = match name {
+                    "left" => Associativity::Left,
+                    "right" => Associativity::Right,
+                    _ => Associativity::None,
+                };
+                let ids = self.ident_list()?;
+                self.push_decl(loc, Decl::Assoc(assoc, ids));
+            }
+            "token" => {
+                for id in self.ident_list()? {
+                    self.push_decl(loc, Decl::Type(Vec::new(), id, Vec::new(), None));
+                }
+            }
+            "fallback" => {
+                let fb = self.ident()?;
+                let ids = self.ident_list()?;
+                self.push_decl(loc, Decl::Fallback(fb, ids));
+            }
+            "wildcard" => {
+                let id = self.ident()?;
+                self.next_if(Tok::Punct('.'));
+                self.push_decl(loc, Decl::Wildcard(id));
+            }
+            "token_class" => {
+                let class_loc = self.loc();
+                let class = self.ident_name()?;
+                let mut renamed = class.clone();
+                renamed[..1].make_ascii_uppercase();
+                let tk = self.make_ident(&renamed, class_loc)?;
+                let mut ids = vec![self.ident()?];
+                while self.next_if(Tok::Punct('|')) || self.next_if(Tok::Punct('/')) {
+                    ids.push(self.ident()?);
+                }
+                self.next_if(Tok::Punct('.'));
+                self.classes.insert(class, renamed);
+                self.push_decl(loc, Decl::TokenClass(tk, ids));
+            }
+            "start_symbol" => {
+                let id = self.ident()?;
+                self.next_if(Tok::Punct('.'));
+                self.push_decl(loc, Decl::StartSymbol(id, Vec::new()));
+            }
+            "stack_size" => {
+                let size_loc = self.loc();
+                let size = match self.next() {
+                    Some(Tok::Ident(size)) => size.parse::<usize>().ok(),
+                    _ => None,
+                };
+                match size {
+                    Some(size) => self.push_decl(loc, Decl::StackSize(Some(size), None)),
+                    None => return self.error(size_loc, "Invalid stack size"),
+                }
+                self.next_if(Tok::Punct('.'));
+            }
+            //Names of the generated C code
+            "name" | "token_prefix" => {
+                self.skip_arg()?;
+                let msg = format!("%{} is only used by the C code, remove it", name);
+                self.c_errors.push(self.make_error(loc, &msg)); //tested
+            }
+            //C code or C types, the Rust equivalent must be written in the pomelo! macro
+            "include" | "code" | "syntax_error" | "parse_failure" | "parse_accept"
+            | "stack_overflow" | "token_destructor" | "default_destructor" | "token_type"
+            | "default_type" | "extra_argument" | "extra_context" | "realloc" | "free"
+            | "type" | "destructor" => {
+                if name == "type" || name == "destructor" {
+                    self.ident_name()?;
+                }
+                self.skip_arg()?;
+                let msg = format!("%{} is C code, declare it in pomelo!", name);
+                self.c_errors.push(self.make_error(loc, &msg)); //tested
+            }
+            _ => return self.error(loc, &format!("Unknown directive %{}", name)), //tested
+        }
+        Ok(())
+    }
+
+    // rule: lhs[(A)] ::= sym1[(B)] sym2|sym3[(C)] ... . [[PREC]] [{ code }]
+    fn rule(&mut self) -> Result<()> {
+        let loc = self.loc();
+        let lhs = self.ident()?;
+        self.alias()?;
+        if !self.next_if(Tok::Assign) {
+            return self.error(self.loc(), "`::=` expected");
+        }
+        let mut rhs = Vec::new();
+        while !self.next_if(Tok::Punct('.')) {
+            let mut sym = vec![self.ident()?];
+            while self.next_if(Tok::Punct('|')) || self.next_if(Tok::Punct('/')) {
+                sym.push(self.ident()?);
+            }
+            self.alias()?;
+            rhs.push(sym);
+        }
+        let prec = if self.next_if(Tok::Punct('[')) {
+            let prec = self.ident()?;
+            if !self.next_if(Tok::Punct(']')) {
+                return self.error(self.loc(), "`]` expected");
+            }
+            Some(prec)
+        } else {
+            None
+        };
+        let has_code = match self.peek() {
+            Some(Tok::Code(code)) => {
+                let has_code = !code.trim().is_empty();
+                self.pos += 1;
+                has_code
+            }
+            _ => false,
+        };
+        self.rules.push(LemonRule {
+            loc,
+            lhs,
+            rhs,
+            has_code,
+            prec,
+        });
+        Ok(())
+    }
+
+    //Aliases are names of C variables, so they are discarded
+    fn alias(&mut self) -> Result<()> {
+        if self.next_if(Tok::Punct('(')) {
+            self.ident_name()?;
+            if !self.next_if(Tok::Punct(')')) {
+                return self.error(self.loc(), "`)` expected");
+            }
+        }
+        Ok(())
+    }
+}
+
+//LHS and RHS of a rule, to match the Lemon rules with the Rust ones
+type RuleKey = (String, Vec<Vec<String>>);
+
+fn rule_key(lhs: &Ident, rhs: &[Vec<Ident>]) -> RuleKey {
+    let rhs = rhs
+        .iter()
+        .map(|sym| sym.iter().map(|s| s.to_string()).collect())
+        .collect();
+    (lhs.to_string(), rhs)
+}
+
+fn rule_text((lhs, rhs): &RuleKey) -> String {
+    let rhs = rhs.iter().map(|sym| sym.join("|")).collect::<Vec<_>>();
+    format!("{} ::= {}", lhs, rhs.join(" "))
+}
+
+//Reads the Lemon grammar in `path`. The rules also in the `actions` file take the actions from
+//there, and all the other declarations in that file are added too.
+pub fn parse_lemon_file(
+    path: &LitStr,
+    actions: Option<&LitStr>,
+    templates: &[String],
+    depth: usize,
+) -> Result<Vec<(Decl, Location)>> {
+    let (_, text) = crate::read_grammar_file(path, depth)?;
+    let mut parser = LemonParser {
+        path: path.value(),
+        span: path.span(),
+        toks: Vec::new(),
+        pos: 0,
+        ifdefs: Vec::new(),
+        classes: BTreeMap::new(),
+        names: HashSet::new(),
+        decls: Vec::new(),
+        rules: Vec::new(),
+        c_errors: Vec::new(),
+    };
+    parser.toks = match tokenize(&text) {
+        Ok(toks) => toks,
+        Err((loc, msg)) => return parser.error(loc, msg),
+    };
+    parser.parse()?;
+
+    let mut decls = std::mem::take(&mut parser.decls);
+    let mut actions_rules = Vec::new();
+    if let Some(actions) = actions {
+        let text = crate::read_grammar_file(actions, depth)?;
+        for (decl, location) in crate::parse_grammar_file(actions, text, templates, depth)? {
+            match decl {
+                Decl::Rule {
+                    lhs,
+                    params,
+                    rhs,
+                    action,
+                    prec,
+                } => {
+                    //Only rules with plain symbols can match
+                    let plain = rhs
+                        .iter()
+                        .map(|rs| match (&rs.term, rs.quantifier, &rs.separator) {
+                            (RhsTerm::Symbol(toks), Quantifier::One, None) => Some(toks.clone()),
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<_>>>();
+                    match plain {
+                        Some(plain) if params.is_empty() => {
+                            let key = rule_key(&lhs, &plain);
+                            actions_rules.push((key, lhs, rhs, action, prec, location));
+                        }
+                        _ => {
+                            let msg = format!("Rule for `{}` is not in the Lemon grammar", lhs);
+                            return Err(Error::new(actions.span(), msg));
+                        }
+                    }
+                }
+                decl => decls.push((decl, location)),
+            }
+        }
+    }
+
+    for rule in std::mem::take(&mut parser.rules) {
+        let key = rule_key(&rule.lhs, &rule.rhs);
+        match actions_rules.iter().position(|(k, ..)| *k == key) {
+            Some(i) => {
+                let (_, lhs, rhs, action, prec, location) = actions_rules.remove(i);
+                let rule = Decl::Rule {
+                    lhs,
+                    params: Vec::new(),
+                    rhs,
+                    action,
+                    prec: prec.or(rule.prec),
+                };
+                decls.push((rule, location));
+            }
+            None => {
+                let action: Option<Block> = if rule.has_code {
+                    let text = format!("{}:{}: {}", parser.path, rule.loc.0, rule_text(&key));
+                    Some(parse_quote!({ unimplemented!(#text) }))
+                } else {
+                    None
+                };
+                let rhs = rule
+                    .rhs
+                    .into_iter()
+                    .map(|sym| RhsSymbol {
+                        term: RhsTerm::Symbol(sym),
+                        quantifier: Quantifier::One,
+                        separator: None,
+                        alias: None,
+                    })
+                    .collect();
+                let location = parser.location(rule.loc);
+                let rule = Decl::Rule {
+                    lhs: rule.lhs,
+                    params: Vec::new(),
+                    rhs,
+                    action,
+                    prec: rule.prec,
+                };
+                decls.push((rule, location));
+            }
+        }
+    }
+    if let (Some(actions), Some((key, ..))) = (actions, actions_rules.first()) {
+        let msg = format!("Rule `{}` is not in the Lemon grammar", rule_text(key));
+        return Err(Error::new(actions.span(), msg));
+    }
+    Ok(decls)
+}
@@ -7,73 +7,533 @@ extern crate syn;
 extern crate quote;
 
 mod decl;
+mod lemon;
 mod parser;
 
 use decl::*;
 
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use std::path::Path;
+use std::rc::Rc;
 use syn::parse::{Error, Parse, ParseStream, Result};
 use syn::punctuated::Punctuated;
-use syn::{token, Ident, LitInt, Type, Attribute};
+use syn::spanned::Spanned;
+use syn::{token, Attribute, Block, Ident, LitInt, LitStr, Type};
 
 #[doc(hidden)]
 #[proc_macro]
 pub fn pomelo_impl(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let Decls(decls) = parse_macro_input!(input);
-    match pomelo_impl2(decls) {
-        Ok(x) => x,
+    let Decls(decls, files) = parse_macro_input!(input);
+    match pomelo_impl2(decls, files) {
+        Ok(x) => x.into(),
         Err(e) => e.to_compile_error().into(),
     }
 }
 
-fn pomelo_impl2(decls: Vec<Decl>) -> syn::Result<proc_macro::TokenStream> {
+fn pomelo_impl2(decls: Vec<(Decl, Location)>, files: Vec<String>) -> syn::Result<TokenStream> {
     let mut pomelo = parser::Pomelo::new_from_decls(decls)?;
     let expanded = pomelo.build()?;
     let name = pomelo.module_name();
     let x = quote! {
         mod #name {
             #expanded
+            //So that the crate is rebuilt when an included grammar changes
+            #(const _: &[u8] = include_bytes!(#files);)*
         }
     };
-    Ok(x.into())
+    Ok(x)
 }
 
-struct Decls(Vec<Decl>);
+//The declarations, with their location if they are in an included file, and the full paths of
+//the included grammar files
+struct Decls(Vec<(Decl, Location)>, Vec<String>);
 
 impl Parse for Decls {
     fn parse(input: ParseStream) -> Result<Decls> {
-        let mut decls = Vec::new();
-        while !input.is_empty() {
-            decls.push(input.parse()?);
+        let mut files = Vec::new();
+        let templates = find_templates(&input.fork(), &mut files, 0, None)?;
+        let decls = parse_decls(input, &templates, 0, None)?;
+        Ok(Decls(decls, files))
+    }
+}
+
+fn parse_decls(
+    input: ParseStream,
+    templates: &[String],
+    depth: usize,
+    file: Option<&GrammarFile>,
+) -> Result<Vec<(Decl, Location)>> {
+    let mut decls = Vec::new();
+    let location = file.map(|file| file.path.clone());
+    while !input.is_empty() {
+        let located = |e| parser::add_location(&location, e);
+        if input.peek(Token![%]) && input.peek2(kw::include_grammar) {
+            // %include_grammar "path";
+            let path = parse_include_grammar(input).map_err(located)?;
+            let text = read_grammar_file(&path, depth + 1).map_err(located)?;
+            decls.extend(parse_grammar_file(&path, text, templates, depth + 1)?);
+        } else if input.peek(Token![%]) && input.peek2(kw::include_lemon) {
+            // %include_lemon "path" ["actions"];
+            let (path, actions) = parse_include_lemon(input).map_err(located)?;
+            let lemon = lemon::parse_lemon_file(&path, actions.as_ref(), templates, depth + 1)?;
+            decls.extend(lemon);
+        } else {
+            let decl = Decl::parse(input, templates).map_err(located)?;
+            decls.push((decl, location.clone()));
+        }
+    }
+    Ok(decls)
+}
+
+fn parse_include_grammar(input: ParseStream) -> Result<LitStr> {
+    input.parse::<Token![%]>()?;
+    input.parse::<kw::include_grammar>()?;
+    let path = input.parse::<LitStr>()?;
+    input.parse::<Token![;]>()?;
+    Ok(path)
+}
+
+fn parse_include_lemon(input: ParseStream) -> Result<(LitStr, Option<LitStr>)> {
+    input.parse::<Token![%]>()?;
+    input.parse::<kw::include_lemon>()?;
+    let path = input.parse::<LitStr>()?;
+    let actions = if input.peek(LitStr) {
+        Some(input.parse::<LitStr>()?)
+    } else {
+        None
+    };
+    input.parse::<Token![;]>()?;
+    Ok((path, actions))
+}
+
+const MAX_INCLUDE_DEPTH: usize = 32;
+
+//Reads a grammar file, relative to the directory of the crate being compiled.
+//Returns the full path and the contents.
+fn read_grammar_file(path: &LitStr, depth: usize) -> Result<(String, String)> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(Error::new(path.span(), "Too many nested %include_grammar"));
+    }
+    let mut full_path = path.value();
+    if let Ok(dir) = std::env::var("CARGO_MANIFEST_DIR") {
+        full_path = Path::new(&dir)
+            .join(full_path)
+            .to_string_lossy()
+            .into_owned();
+    }
+    match std::fs::read_to_string(&full_path) {
+        Ok(text) => Ok((full_path, text)),
+        Err(e) => Err(Error::new(
+            path.span(),
+            format!("Cannot read {}: {}", full_path, e),
+        )), //tested
+    }
+}
+
+//The tokens read from the file cannot have spans that point into it, so they all take the span
+//of the path in the `%include_grammar` directive, and the errors tell the file in their message.
+fn parse_grammar_file(
+    path: &LitStr,
+    (_, text): (String, String),
+    templates: &[String],
+    depth: usize,
+) -> Result<Vec<(Decl, Location)>> {
+    let file = GrammarFile::new(path.value(), &text).map_err(|msg| Error::new(path.span(), msg))?;
+    let tokens = respan(file.tokens.clone(), path.span());
+    syn::parse::Parser::parse2(
+        |input: ParseStream| parse_decls(input, templates, depth, Some(&file)),
+        tokens,
+    )
+}
+
+//An included grammar file, already split into tokens
+struct GrammarFile {
+    path: Rc<str>,
+    tokens: TokenStream,
+}
+
+impl GrammarFile {
+    fn new(path: String, text: &str) -> std::result::Result<GrammarFile, String> {
+        match text.parse::<TokenStream>() {
+            Ok(tokens) => Ok(GrammarFile {
+                path: path.into(),
+                tokens,
+            }),
+            Err(e) => Err(format!("{}: {}", path, e)),
         }
-        Ok(Decls(decls))
     }
 }
 
+fn respan(tokens: TokenStream, span: Span) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|mut tt| {
+            if let TokenTree::Group(g) = &tt {
+                let mut ng = proc_macro2::Group::new(g.delimiter(), respan(g.stream(), span));
+                ng.set_span(span);
+                tt = TokenTree::Group(ng);
+            } else {
+                tt.set_span(span);
+            }
+            tt
+        })
+        .collect()
+}
+
 mod kw {
     custom_keyword!(module);
     custom_keyword!(include);
+    custom_keyword!(include_grammar);
+    custom_keyword!(include_lemon);
     custom_keyword!(syntax_error);
     custom_keyword!(parse_fail);
     custom_keyword!(stack_overflow);
+    custom_keyword!(destructor);
+    custom_keyword!(token_destructor);
     custom_keyword!(left);
     custom_keyword!(right);
     custom_keyword!(nonassoc);
+    custom_keyword!(precedence);
     custom_keyword!(default_type);
     custom_keyword!(extra_argument);
     custom_keyword!(error);
     custom_keyword!(start_symbol);
+    custom_keyword!(inline);
     custom_keyword!(fallback);
     custom_keyword!(wildcard);
     custom_keyword!(token_class);
     custom_keyword!(token);
     custom_keyword!(verbose);
+    custom_keyword!(algorithm);
+    custom_keyword!(tables);
+    custom_keyword!(glr);
+    custom_keyword!(merge);
+    custom_keyword!(deny);
+    custom_keyword!(allow);
+    custom_keyword!(strict);
+    custom_keyword!(terminal);
+    custom_keyword!(nonterminal);
+    custom_keyword!(expect);
+    custom_keyword!(expect_rr);
     custom_keyword!(extra_token);
     custom_keyword!(stack_size);
+    custom_keyword!(auto);
     custom_keyword!(parser);
 }
 
-impl Parse for Decl {
-    fn parse(input: ParseStream) -> Result<Decl> {
+//Names of the parameterized non-terminals, those with rules such as `list(X) ::= ...`.
+//They are needed beforehand to tell `list(X)` from a symbol with an alias in the RHS.
+//Included grammar files are scanned too, and their paths are added to `files`.
+fn find_templates(
+    input: ParseStream,
+    files: &mut Vec<String>,
+    depth: usize,
+    file: Option<&GrammarFile>,
+) -> Result<Vec<String>> {
+    let tokens = input
+        .parse::<TokenStream>()?
+        .into_iter()
+        .collect::<Vec<_>>();
+    let mut templates = Vec::new();
+    let location = file.map(|file| file.path.clone());
+    for w in tokens.windows(3) {
+        let read_file = |lit: &proc_macro2::Literal| -> Result<(LitStr, (String, String))> {
+            let path = syn::parse2::<LitStr>(TokenTree::Literal(lit.clone()).into())?;
+            let text = read_grammar_file(&path, depth + 1)
+                .map_err(|e| parser::add_location(&location, e))?;
+            Ok((path, text))
+        };
+        match w {
+            [TokenTree::Ident(id), TokenTree::Group(g), TokenTree::Punct(p)]
+                if g.delimiter() == Delimiter::Parenthesis && p.as_char() == ':' =>
+            {
+                templates.push(id.to_string());
+            }
+            [TokenTree::Punct(p), TokenTree::Ident(id), TokenTree::Literal(lit)]
+                if p.as_char() == '%' && id == "include_grammar" =>
+            {
+                let (path, text) = read_file(lit)?;
+                templates.extend(find_templates_in_file(&path, text, files, depth + 1)?);
+            }
+            [TokenTree::Punct(p), TokenTree::Ident(id), TokenTree::Literal(lit)]
+                if p.as_char() == '%' && id == "include_lemon" =>
+            {
+                files.push(read_file(lit)?.1 .0);
+            }
+            //The file with the actions of a Lemon grammar
+            [TokenTree::Ident(id), TokenTree::Literal(_), TokenTree::Literal(lit)]
+                if id == "include_lemon" =>
+            {
+                let (path, text) = read_file(lit)?;
+                templates.extend(find_templates_in_file(&path, text, files, depth + 1)?);
+            }
+            _ => (),
+        }
+    }
+    Ok(templates)
+}
+
+fn find_templates_in_file(
+    path: &LitStr,
+    (full_path, text): (String, String),
+    files: &mut Vec<String>,
+    depth: usize,
+) -> Result<Vec<String>> {
+    files.push(full_path);
+    //Syntax errors are reported later, when the file is actually parsed
+    match GrammarFile::new(path.value(), &text) {
+        Ok(file) => syn::parse::Parser::parse2(
+            |input: ParseStream| find_templates(input, files, depth, Some(&file)),
+            respan(file.tokens.clone(), path.span()),
+        ),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+// rhs:  id1|id2[?|*|+][% sep1|sep2[?]][(alias)]
+//        (rhs1 rhs2... | rhs3...)[?|*|+][% sep[?]][(alias)]
+//        template(arg1, arg2...)[?|*|+][% sep[?]][(alias)]
+//        { code }[(alias[: type])]
+//Inside a group `|` separates alternatives, so compound symbols and separators are single tokens
+//and there are no mid-rule actions
+fn parse_rhs(input: ParseStream, in_group: bool, templates: &[String]) -> Result<Vec<RhsSymbol>> {
+    let mut rhs = Vec::new();
+    loop {
+        let term = if input.peek(Ident) && input.peek2(token::Paren) && {
+            let id = input.fork().parse::<Ident>()?;
+            templates.iter().any(|t| id == t)
+        } {
+            let id = input.parse()?;
+            RhsTerm::Apply(id, parse_args(input, templates)?)
+        } else if input.peek(Ident) {
+            if in_group {
+                RhsTerm::Symbol(vec![input.parse()?])
+            } else {
+                let toks = Punctuated::<Ident, Token![|]>::parse_separated_nonempty(input)?;
+                RhsTerm::Symbol(toks.into_iter().collect())
+            }
+        } else if input.peek(token::Paren) {
+            let span = input.span();
+            RhsTerm::Group(span, parse_group(input, templates)?)
+        } else if !in_group && peek_mid_rule_action(input) {
+            let code = input.parse()?;
+            if input.peek(Token![?]) || input.peek(Token![*]) || input.peek(Token![+]) {
+                return Err(input.error("A mid-rule action cannot be repeated"));
+                //tested
+            }
+            //`{ code }(alias: Type)`: the type is part of the action, the pattern is the alias
+            if input.peek(token::Paren) && !peek_group(input, templates) {
+                let sub;
+                parenthesized!(sub in input);
+                let alias = sub.parse()?;
+                let ty = if sub.peek(Token![:]) {
+                    sub.parse::<Token![:]>()?;
+                    Some(sub.parse()?)
+                } else {
+                    None
+                };
+                rhs.push(RhsSymbol {
+                    term: RhsTerm::Action(code, ty),
+                    quantifier: Quantifier::One,
+                    separator: None,
+                    alias: Some(alias),
+                });
+            } else {
+                rhs.push(RhsSymbol {
+                    term: RhsTerm::Action(code, None),
+                    quantifier: Quantifier::One,
+                    separator: None,
+                    alias: None,
+                });
+            }
+            continue;
+        } else {
+            break;
+        };
+        let mut quantifier = if input.peek(Token![?]) {
+            input.parse::<Token![?]>()?;
+            Quantifier::Optional
+        } else if input.peek(Token![*]) {
+            input.parse::<Token![*]>()?;
+            Quantifier::ZeroOrMore
+        } else if input.peek(Token![+]) {
+            input.parse::<Token![+]>()?;
+            Quantifier::OneOrMore
+        } else {
+            Quantifier::One
+        };
+        //A `%` followed by a token is a separator, else it is the next directive
+        let separator = if peek_separator(input) {
+            let pc = input.parse::<Token![%]>()?;
+            let seps = if in_group {
+                vec![input.parse()?]
+            } else {
+                let seps = Punctuated::<Ident, Token![|]>::parse_separated_nonempty(input)?;
+                seps.into_iter().collect()
+            };
+            let trailing = if input.peek(Token![?]) {
+                input.parse::<Token![?]>()?;
+                true
+            } else {
+                false
+            };
+            quantifier = match quantifier {
+                Quantifier::One => Quantifier::OneOrMore,
+                Quantifier::Optional => {
+                    return Err(Error::new(
+                        pc.span,
+                        "A separated list must be repeated with `*` or `+`",
+                    ));
+                }
+                q => q,
+            };
+            Some((seps, trailing))
+        } else {
+            None
+        };
+        let alias = if input.peek(token::Paren) && !peek_group(input, templates) {
+            let sub;
+            parenthesized!(sub in input);
+            Some(sub.parse()?)
+        } else {
+            None
+        };
+        rhs.push(RhsSymbol {
+            term,
+            quantifier,
+            separator,
+            alias,
+        });
+    }
+    Ok(rhs)
+}
+
+fn parse_group(input: ParseStream, templates: &[String]) -> Result<Vec<Vec<RhsSymbol>>> {
+    let sub;
+    parenthesized!(sub in input);
+    let alts = parse_alternatives(&sub, templates)?;
+    if !sub.is_empty() {
+        return Err(sub.error("`|` or `)` expected"));
+    }
+    Ok(alts)
+}
+
+fn parse_alternatives(input: ParseStream, templates: &[String]) -> Result<Vec<Vec<RhsSymbol>>> {
+    let mut alts = vec![parse_rhs(input, true, templates)?];
+    while input.peek(Token![|]) {
+        input.parse::<Token![|]>()?;
+        alts.push(parse_rhs(input, true, templates)?);
+    }
+    Ok(alts)
+}
+
+//The arguments of a parameterized non-terminal, separated by commas.
+//An argument with several symbols is a group: `list(Comma expr)` == `list((Comma expr))`
+fn parse_args(input: ParseStream, templates: &[String]) -> Result<Vec<RhsSymbol>> {
+    let sub;
+    parenthesized!(sub in input);
+    let mut args = Vec::new();
+    loop {
+        let span = sub.span();
+        let mut arg = parse_rhs(&sub, false, templates)?;
+        if let Some(RhsSymbol {
+            term: RhsTerm::Action(code, _),
+            ..
+        }) = arg.iter().find(|rs| matches!(rs.term, RhsTerm::Action(..)))
+        {
+            return Err(Error::new(
+                code.span(),
+                "Mid-rule actions are not allowed in arguments",
+            ));
+        }
+        let arg = match arg.len() {
+            0 => return Err(Error::new(span, "Argument expected")),
+            1 => arg.pop().unwrap(),
+            _ => RhsSymbol {
+                term: RhsTerm::Group(span, vec![arg]),
+                quantifier: Quantifier::One,
+                separator: None,
+                alias: None,
+            },
+        };
+        args.push(arg);
+        if sub.is_empty() {
+            break;
+        }
+        sub.parse::<Token![,]>()?;
+    }
+    Ok(args)
+}
+
+//After a symbol, a parenthesized list is its alias, unless it is a valid group.
+//A single identifier is always an alias, and groups cannot have aliases inside.
+fn peek_group(input: ParseStream, templates: &[String]) -> bool {
+    fn has_alias(rhs: &[RhsSymbol]) -> bool {
+        rhs.iter().any(|rs| {
+            rs.alias.is_some()
+                || match &rs.term {
+                    RhsTerm::Group(_, alts) => alts.iter().any(|alt| has_alias(alt)),
+                    RhsTerm::Apply(_, args) => has_alias(args),
+                    RhsTerm::Symbol(_) | RhsTerm::Action(..) => false,
+                }
+        })
+    }
+    match parse_group(&input.fork(), templates) {
+        Ok(alts) => {
+            let single_ident = matches!(
+                &alts[..],
+                [alt] if matches!(
+                    &alt[..],
+                    [RhsSymbol {
+                        term: RhsTerm::Symbol(_),
+                        quantifier: Quantifier::One,
+                        separator: None,
+                        alias: None,
+                    }]
+                )
+            );
+            !single_ident && !alts.iter().any(|alt| has_alias(alt))
+        }
+        Err(_) => false,
+    }
+}
+
+//A block in the RHS is a mid-rule action if something else of the rule follows it, else it is the
+//action of the rule. The next rule may follow without a `;`, so `lhs ::=` is not a symbol.
+fn peek_mid_rule_action(input: ParseStream) -> bool {
+    let fork = input.fork();
+    if !fork.peek(token::Brace) || fork.parse::<Block>().is_err() {
+        return false;
+    }
+    if fork.peek(token::Paren) || fork.peek(token::Brace) || fork.peek(token::Bracket) {
+        return true;
+    }
+    //Not valid, but it is reported better as a mid-rule action
+    if fork.peek(Token![?]) || fork.peek(Token![*]) || fork.peek(Token![+]) {
+        return true;
+    }
+    if fork.parse::<Ident>().is_err() {
+        return false;
+    }
+    //`lhs(X, Y) ::=`
+    if fork.peek(token::Paren) {
+        let _ = fork.parse::<TokenTree>();
+    }
+    !fork.peek(Token![::])
+}
+
+//Separators are tokens, so they start with uppercase, while directives are all lowercase
+fn peek_separator(input: ParseStream) -> bool {
+    let fork = input.fork();
+    fork.parse::<Token![%]>().is_ok()
+        && fork
+            .parse::<Ident>()
+            .map(|id| id.to_string().starts_with(|c: char| c.is_ascii_uppercase()))
+            .unwrap_or(false)
+}
+
+impl Decl {
+    fn parse(input: ParseStream, templates: &[String]) -> Result<Decl> {
         if input.peek(Token![%]) {
             input.parse::<Token![%]>()?;
             let lookahead = input.lookahead1();
@@ -82,6 +542,16 @@ impl Parse for Decl {
                 input.parse::<Token![type]>()?;
                 let attrs = input.call(Attribute::parse_outer)?;
                 let ident = input.parse::<Ident>()?;
+                //`%type list(X) Vec<X>;`, but `%type pair (i32, i32);` is a tuple
+                let params = if input.peek(token::Paren) && !input.peek2(Token![;]) {
+                    let sub;
+                    parenthesized!(sub in input);
+                    Punctuated::<Ident, Token![,]>::parse_separated_nonempty(&sub)?
+                        .into_iter()
+                        .collect()
+                } else {
+                    Vec::new()
+                };
                 let lookahead = input.lookahead1();
                 let typ = if lookahead.peek(Token![;]) {
                     None
@@ -89,7 +559,7 @@ impl Parse for Decl {
                     Some(input.parse::<Type>()?)
                 };
                 input.parse::<Token![;]>()?;
-                Ok(Decl::Type(attrs, ident, typ))
+                Ok(Decl::Type(attrs, ident, params, typ))
             } else if lookahead.peek(kw::module) {
                 input.parse::<kw::module>()?;
                 // %module ident;
@@ -133,6 +603,30 @@ impl Parse for Decl {
                     input.parse::<Token![;]>()?;
                 }
                 Ok(Decl::StackOverflow(code))
+            } else if lookahead.peek(kw::destructor) {
+                // %destructor id[(alias)] { rust-block }
+                input.parse::<kw::destructor>()?;
+                let id = input.parse()?;
+                let alias = if input.peek(token::Paren) {
+                    let sub;
+                    parenthesized!(sub in input);
+                    Some(sub.parse()?)
+                } else {
+                    None
+                };
+                let code = input.parse()?;
+                if input.peek(Token![;]) {
+                    input.parse::<Token![;]>()?;
+                }
+                Ok(Decl::Destructor(id, alias, code))
+            } else if lookahead.peek(kw::token_destructor) {
+                // %token_destructor { rust-block }
+                input.parse::<kw::token_destructor>()?;
+                let code = input.parse()?;
+                if input.peek(Token![;]) {
+                    input.parse::<Token![;]>()?;
+                }
+                Ok(Decl::TokenDestructor(code))
             } else if lookahead.peek(kw::left) {
                 // %left token1 token2 ... ;
                 input.parse::<kw::left>()?;
@@ -160,6 +654,15 @@ impl Parse for Decl {
                 }
                 input.parse::<Token![;]>()?;
                 Ok(Decl::Assoc(Associativity::None, toks))
+            } else if lookahead.peek(kw::precedence) {
+                // %precedence token1 token2 ... ;
+                input.parse::<kw::precedence>()?;
+                let mut toks = Vec::new();
+                while !input.peek(Token![;]) {
+                    toks.push(input.parse()?);
+                }
+                input.parse::<Token![;]>()?;
+                Ok(Decl::Assoc(Associativity::Precedence, toks))
             } else if lookahead.peek(kw::default_type) {
                 // %default_type type;
                 input.parse::<kw::default_type>()?;
@@ -179,11 +682,24 @@ impl Parse for Decl {
                 input.parse::<Token![;]>()?;
                 Ok(Decl::Error(typ))
             } else if lookahead.peek(kw::start_symbol) {
-                // %start_symbol id;
+                // %start_symbol id [id2 id3 ...];
                 input.parse::<kw::start_symbol>()?;
                 let id = input.parse()?;
+                let mut others = Vec::new();
+                while !input.peek(Token![;]) {
+                    others.push(input.parse()?);
+                }
                 input.parse::<Token![;]>()?;
-                Ok(Decl::StartSymbol(id))
+                Ok(Decl::StartSymbol(id, others))
+            } else if lookahead.peek(kw::inline) {
+                // %inline id1 id2 ... ;
+                input.parse::<kw::inline>()?;
+                let mut ids = Vec::new();
+                while !input.peek(Token![;]) {
+                    ids.push(input.parse()?);
+                }
+                input.parse::<Token![;]>()?;
+                Ok(Decl::Inline(ids))
             } else if lookahead.peek(kw::fallback) {
                 // %fallback id_fall id1 id2 ... ;
                 input.parse::<kw::fallback>()?;
@@ -223,9 +739,14 @@ impl Parse for Decl {
                 input.parse::<Token![;]>()?;
                 Ok(Decl::ExtraToken(typ))
             } else if lookahead.peek(kw::stack_size) {
-                // %stack_size limit [type];
+                // %stack_size limit|auto [type];
                 input.parse::<kw::stack_size>()?;
-                let limit = input.parse::<LitInt>()?.base10_parse::<usize>()?;
+                let limit = if input.peek(kw::auto) {
+                    input.parse::<kw::auto>()?;
+                    None
+                } else {
+                    Some(input.parse::<LitInt>()?.base10_parse::<usize>()?)
+                };
                 let typ = if input.peek(Token![;]) {
                     None
                 } else {
@@ -238,6 +759,90 @@ impl Parse for Decl {
                 input.parse::<kw::verbose>()?;
                 input.parse::<Token![;]>()?;
                 Ok(Decl::Verbose)
+            } else if lookahead.peek(kw::algorithm) {
+                // %algorithm lalr|lr1|minimal;
+                input.parse::<kw::algorithm>()?;
+                let id = input.parse()?;
+                input.parse::<Token![;]>()?;
+                Ok(Decl::Algorithm(id))
+            } else if lookahead.peek(kw::tables) {
+                // %tables compressed|dense;
+                input.parse::<kw::tables>()?;
+                let id = input.parse()?;
+                input.parse::<Token![;]>()?;
+                Ok(Decl::Tables(id))
+            } else if lookahead.peek(kw::glr) {
+                // %glr;
+                input.parse::<kw::glr>()?;
+                input.parse::<Token![;]>()?;
+                Ok(Decl::Glr)
+            } else if lookahead.peek(kw::strict) {
+                // %strict;
+                input.parse::<kw::strict>()?;
+                input.parse::<Token![;]>()?;
+                Ok(Decl::Strict)
+            } else if lookahead.peek(kw::terminal) {
+                // %terminal id1 id2 ... ;
+                input.parse::<kw::terminal>()?;
+                let mut ids = Vec::new();
+                while !input.peek(Token![;]) {
+                    ids.push(input.parse()?);
+                }
+                input.parse::<Token![;]>()?;
+                Ok(Decl::Terminal(ids))
+            } else if lookahead.peek(kw::nonterminal) {
+                // %nonterminal id1 id2 ... ;
+                input.parse::<kw::nonterminal>()?;
+                let mut ids = Vec::new();
+                while !input.peek(Token![;]) {
+                    ids.push(input.parse()?);
+                }
+                input.parse::<Token![;]>()?;
+                Ok(Decl::NonTerminal(ids))
+            } else if lookahead.peek(kw::deny) {
+                // %deny lint1 lint2 ... ;
+                input.parse::<kw::deny>()?;
+                let mut ids = Vec::new();
+                while !input.peek(Token![;]) {
+                    ids.push(input.parse()?);
+                }
+                input.parse::<Token![;]>()?;
+                Ok(Decl::Deny(ids))
+            } else if lookahead.peek(kw::allow) {
+                // %allow lint1 lint2 ... ;
+                input.parse::<kw::allow>()?;
+                let mut ids = Vec::new();
+                while !input.peek(Token![;]) {
+                    ids.push(input.parse()?);
+                }
+                input.parse::<Token![;]>()?;
+                Ok(Decl::Allow(ids))
+            } else if lookahead.peek(kw::merge) {
+                // %merge id(alias1, alias2) { rust-block }
+                input.parse::<kw::merge>()?;
+                let id = input.parse()?;
+                let sub;
+                parenthesized!(sub in input);
+                let a = sub.parse()?;
+                sub.parse::<Token![,]>()?;
+                let b = sub.parse()?;
+                let code = input.parse()?;
+                if input.peek(Token![;]) {
+                    input.parse::<Token![;]>()?;
+                }
+                Ok(Decl::Merge(id, a, b, code))
+            } else if lookahead.peek(kw::expect) {
+                // %expect num;
+                input.parse::<kw::expect>()?;
+                let n = input.parse()?;
+                input.parse::<Token![;]>()?;
+                Ok(Decl::Expect(n))
+            } else if lookahead.peek(kw::expect_rr) {
+                // %expect_rr num;
+                input.parse::<kw::expect_rr>()?;
+                let n = input.parse()?;
+                input.parse::<Token![;]>()?;
+                Ok(Decl::ExpectRR(n))
             } else if lookahead.peek(kw::parser) {
                 // %parser struct;
                 input.parse::<kw::parser>()?;
@@ -248,37 +853,24 @@ impl Parse for Decl {
                 Err(lookahead.error())
             }
         } else {
-            // rule: id ::= rhs1 rhs2 ... [[precedence]] [ { code } ] [;]
-            // rhs:  id1|id2[?][(alias)]
+            // rule: id[(param1, param2...)] ::= rhs1 rhs2 ... [[precedence]] [ { code } ] [;]
             let lhs = input
                 .parse::<Ident>()
                 .map_err(|e| Error::new(e.span(), "% or identifier expected"))?;
+            let params = if input.peek(token::Paren) {
+                let sub;
+                parenthesized!(sub in input);
+                let params = Punctuated::<Ident, Token![,]>::parse_terminated(&sub)?;
+                if params.is_empty() {
+                    return Err(sub.error("Parameter expected"));
+                }
+                params.into_iter().collect()
+            } else {
+                Vec::new()
+            };
             input.parse::<Token![::]>()?;
             input.parse::<Token![=]>()?;
-            let mut rhs = Vec::new();
-            loop {
-                let lookahead = input.lookahead1();
-                if !lookahead.peek(Ident) {
-                    break;
-                }
-                //rhs
-                let toks = Punctuated::<Ident, Token![|]>::parse_separated_nonempty(input)?;
-                let toks = toks.into_iter().collect();
-                let optional = if input.peek(Token![?]) {
-                    input.parse::<Token![?]>()?;
-                    true
-                } else {
-                    false
-                };
-                let alias = if input.peek(token::Paren) {
-                    let sub;
-                    parenthesized!(sub in input);
-                    Some(sub.parse()?)
-                } else {
-                    None
-                };
-                rhs.push((toks, optional, alias));
-            }
+            let rhs = parse_rhs(input, false, templates)?;
             let prec = if input.peek(token::Bracket) {
                 let sub;
                 bracketed!(sub in input);
@@ -296,6 +888,7 @@ impl Parse for Decl {
             }
             Ok(Decl::Rule {
                 lhs,
+                params,
                 rhs,
                 action,
                 prec,
@@ -303,3 +896,41 @@ impl Parse for Decl {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //The expansion must not depend on the iteration order of any hash map
+    #[test]
+    fn deterministic_expansion() {
+        let grammar = r#"
+            %type Num i32;
+            %type Name String;
+            %type expr i64;
+            %type args Vec<i64>;
+            %type pair(T) (T, T);
+            %left Plus Minus;
+            %left Times;
+
+            input ::= stmt* ;
+            stmt ::= (Let Name Eq)? expr Semi;
+            stmt ::= pair(expr) Semi;
+            stmt ::= pair(Name) Semi;
+            pair(T) ::= LParen T(a) Comma T(b) RParen { (a, b) }
+            expr ::= Num(n) { n as i64 }
+            expr ::= Name LParen args RParen { 0 }
+            expr ::= expr(a) (Plus | Minus) expr(b) { a + b }
+            expr ::= expr(a) Times expr(b) { a * b }
+            args ::= expr* % Comma;
+        "#;
+        let expand = || {
+            let Decls(decls, files) = syn::parse_str(grammar).unwrap();
+            pomelo_impl2(decls, files).unwrap().to_string()
+        };
+        let first = expand();
+        for _ in 0..4 {
+            assert_eq!(first, expand());
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//A set of small integers, the indices of the terminal symbols, stored as a dense bit array.
+//The words are only added when a bit is set in them, so the last word is never zero, and two sets
+//with the same elements are always equal.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+const BITS: usize = u64::BITS as usize;
+
+impl BitSet {
+    pub fn new() -> BitSet {
+        BitSet { words: Vec::new() }
+    }
+    //Returns true if `i` was not in the set
+    pub fn insert(&mut self, i: usize) -> bool {
+        let (w, b) = (i / BITS, 1 << (i % BITS));
+        if w >= self.words.len() {
+            self.words.resize(w + 1, 0);
+        }
+        let old = self.words[w];
+        self.words[w] |= b;
+        old & b == 0
+    }
+    pub fn contains(&self, i: usize) -> bool {
+        match self.words.get(i / BITS) {
+            Some(w) => w & (1 << (i % BITS)) != 0,
+            None => false,
+        }
+    }
+    //Adds all the elements of `other`, returns true if any of them was not in the set
+    pub fn union_with(&mut self, other: &BitSet) -> bool {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        let mut changed = false;
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            changed |= *b & !*a != 0;
+            *a |= *b;
+        }
+        changed
+    }
+}
+
+impl std::iter::FromIterator<usize> for BitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> BitSet {
+        let mut set = BitSet::new();
+        for i in iter {
+            set.insert(i);
+        }
+        set
+    }
+}
+
+//The tests build the parsers with both this set and a BTreeSet, and compare them. The set used is
+//chosen when it is created, with `use_btree_set`.
+#[cfg(test)]
+thread_local! {
+    static BTREE_SET: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+#[cfg(test)]
+pub fn use_btree_set(on: bool) {
+    BTREE_SET.with(|b| b.set(on));
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestSet {
+    Bits(BitSet),
+    Tree(std::collections::BTreeSet<usize>),
+}
+
+#[cfg(test)]
+impl TestSet {
+    pub fn new() -> TestSet {
+        if BTREE_SET.with(|b| b.get()) {
+            TestSet::Tree(Default::default())
+        } else {
+            TestSet::Bits(BitSet::new())
+        }
+    }
+    pub fn insert(&mut self, i: usize) -> bool {
+        match self {
+            TestSet::Bits(s) => s.insert(i),
+            TestSet::Tree(s) => s.insert(i),
+        }
+    }
+    pub fn contains(&self, i: usize) -> bool {
+        match self {
+            TestSet::Bits(s) => s.contains(i),
+            TestSet::Tree(s) => s.contains(&i),
+        }
+    }
+    pub fn union_with(&mut self, other: &TestSet) -> bool {
+        match (self, other) {
+            (TestSet::Bits(s), TestSet::Bits(o)) => s.union_with(o),
+            (TestSet::Tree(s), TestSet::Tree(o)) => {
+                let n = s.len();
+                s.extend(o);
+                s.len() > n
+            }
+            _ => panic!("mixed sets"),
+        }
+    }
+}
+
+#[cfg(test)]
+impl std::iter::FromIterator<usize> for TestSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> TestSet {
+        let mut set = TestSet::new();
+        for i in iter {
+            set.insert(i);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_across_words() {
+        let mut set = BitSet::new();
+        assert!(set.insert(63));
+        assert_eq!(set.words, [1 << 63]);
+        assert!(set.insert(64));
+        assert!(set.insert(65));
+        assert!(!set.insert(64));
+        assert_eq!(set.words, [1 << 63, 0b11]);
+        for i in 0..130 {
+            assert_eq!(set.contains(i), (63..=65).contains(&i), "{}", i);
+        }
+        assert!(set.insert(200));
+        assert_eq!(set.words.len(), 4);
+        assert!(set.contains(200));
+        assert!(!set.contains(1000));
+    }
+
+    #[test]
+    fn union_across_words() {
+        let mut a: BitSet = [1, 63].iter().copied().collect();
+        let b: BitSet = [63, 64, 130].iter().copied().collect();
+        assert!(a.union_with(&b));
+        assert_eq!(a, [1, 63, 64, 130].iter().copied().collect());
+        assert!(!a.union_with(&b));
+        //A shorter set adds nothing and does not shrink the longer one
+        let c: BitSet = [1].iter().copied().collect();
+        assert!(!a.union_with(&c));
+        assert_eq!(a.words.len(), 3);
+        let mut c = c;
+        assert!(c.union_with(&a));
+        assert_eq!(c, a);
+        assert!(!a.union_with(&BitSet::new()));
+    }
+
+    //The last word is never zero, so the same elements are always the same words
+    #[test]
+    fn equality() {
+        let mut a = BitSet::new();
+        let mut b = BitSet::new();
+        assert_eq!(a, b);
+        a.insert(3);
+        b.union_with(&BitSet::new());
+        assert_ne!(a, b);
+        b.insert(3);
+        assert_eq!(a, b);
+        a.insert(100);
+        assert_ne!(a, b);
+        b.union_with(&a);
+        assert_eq!(a, b);
+        for set in [&a, &b] {
+            assert_ne!(set.words.last(), Some(&0));
+        }
+        assert_eq!(BitSet::new(), BitSet::default());
+        assert_eq!(BitSet::new(), std::iter::empty().collect());
+    }
+}
@@ -1,19 +1,23 @@
 use std::borrow::Cow;
 use std::cell::RefCell;
 use std::cmp::{self, Ordering};
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::fmt::{Display, Write};
 use std::hash::{Hash, Hasher};
 
 use crate::decl::*;
-use proc_macro2::{Literal, Span, TokenStream};
+use proc_macro2::{Delimiter, Group, Literal, Span, TokenStream, TokenTree};
 use quote::ToTokens;
 use syn::{spanned::Spanned, Block, Fields, Ident, Item, ItemEnum, ItemStruct, Pat, Type, Variant, Attribute};
 
+mod bitset;
 mod vecref;
 use vecref::*;
 
-type RuleSet = BTreeSet<usize>;
+#[cfg(not(test))]
+type RuleSet = bitset::BitSet;
+#[cfg(test)]
+type RuleSet = bitset::TestSet;
 
 enum NewSymbolType {
     Terminal,
@@ -29,7 +33,7 @@ fn precedence_cmp(a: Precedence, b: Precedence) -> Ordering {
         Ordering::Equal => match a.1 {
             Associativity::Left => Ordering::Less,
             Associativity::Right => Ordering::Greater,
-            Associativity::None => Ordering::Equal,
+            Associativity::None | Associativity::Precedence => Ordering::Equal,
         },
         o => o,
     }
@@ -46,6 +50,49 @@ type ConfigId = VecRefId<Config>;
 #[derive(Debug, Clone)]
 struct SymbolSpan(SymbolId, Span);
 
+//A hidden non-terminal created from an EBNF construct in the RHS of a rule
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Hidden {
+    //The repeated symbol, the quantifier and maybe a separator symbol, that can be trailing or not
+    Ebnf(SymbolId, Quantifier, Option<(SymbolId, bool)>),
+    //A parenthesized group: a list of alternatives, each one a sequence of symbols
+    Group(Vec<Vec<SymbolId>>),
+    //A parameterized non-terminal with its arguments
+    Instance(String, Vec<SymbolId>),
+}
+
+impl Hidden {
+    //Symbols that contribute to the value of this one
+    fn value_symbols(&self) -> Vec<SymbolId> {
+        match self {
+            Hidden::Ebnf(sym, ..) => vec![*sym],
+            Hidden::Group(alts) => alts.iter().flatten().cloned().collect(),
+            Hidden::Instance(_, args) => args.clone(),
+        }
+    }
+}
+
+//A parameterized non-terminal, such as `list(X)`. It is expanded into a new non-terminal for
+//every distinct list of arguments it is used with.
+#[derive(Debug, Default)]
+struct Template {
+    rules: Vec<TemplateRule>,
+    data_type: Option<(Vec<Ident>, Type)>,
+}
+
+#[derive(Debug, Clone)]
+struct TemplateRule {
+    span: Span,
+    params: Vec<Ident>,
+    rhs: Vec<RhsSymbol>,
+    action: Option<Block>,
+    prec: Option<Ident>,
+    location: Location,
+}
+
+//Maximum nesting of parameterized non-terminals, to stop things like `x(X) ::= x(y(X))`
+const MAX_EXPANSION_DEPTH: usize = 64;
+
 //In RHS of a rule, we have symbols, spans and possibly alias
 #[derive(Debug, Clone)]
 struct SymbolAlias(SymbolId, Span, Option<Pat>);
@@ -61,6 +108,7 @@ struct Rule {
     precedence: Option<Precedence>, //Actual precedence for this rule
     index: usize,                   //An index number for this rule
     can_reduce: bool,               //True if this rule is ever reduced
+    location: Location,             //Where the rule is, if it is in an included file
 }
 
 #[derive(Debug)]
@@ -86,6 +134,8 @@ struct Symbol {
     data_type: Option<Type>,        //Data type held by this object
     dt_num: usize, //The data type number (0 is always ()). The YY{} element of stack is the correct data type for this object
     attributes: Vec<Attribute>,
+    decl_span: Option<Span>, //Where the symbol is declared with %type, %terminal or a precedence
+    decl_location: Location, //The same, if it is declared in an included file
 }
 
 impl Symbol {
@@ -142,6 +192,47 @@ fn config_cmp(data: &Pomelo, a: ConfigId, b: ConfigId) -> Ordering {
     config_cmp_key(data, a, bindex, bdot)
 }
 
+//How the states of the parser are built, from the %algorithm directive
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Algorithm {
+    Lalr,    //LALR(1): states with the same core are always merged
+    Lr1,     //Canonical LR(1): states with different lookaheads are never merged
+    Minimal, //Minimal LR(1): states are merged as in LALR(1) unless that adds conflicts
+}
+
+//How the action tables are generated, from the %tables directive
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Tables {
+    Compressed, //Lemon's tables, with the action sets overlapped in a single array
+    Dense,      //A full matrix of states and symbols, bigger but faster
+}
+
+//The problems in the grammar reported by `lint_grammar`, enabled with %deny and disabled with %allow
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Lint {
+    Unreachable,   //Non-terminals not reachable from the start symbol
+    NonProductive, //Non-terminals that never derive a sequence of tokens
+    UnusedToken,   //Tokens declared with a type or precedence but not used
+    DuplicateRule, //The same rule twice
+    Typo,          //Tokens used once that look like another token
+}
+
+impl Lint {
+    fn from_ident(id: &Ident) -> syn::Result<Lint> {
+        match id.to_string().as_str() {
+            "unreachable" => Ok(Lint::Unreachable),
+            "non_productive" => Ok(Lint::NonProductive),
+            "unused_token" => Ok(Lint::UnusedToken),
+            "duplicate_rule" => Ok(Lint::DuplicateRule),
+            "typo" => Ok(Lint::Typo),
+            _ => error_span(
+                id.span(),
+                "Unknown lint, expected unreachable, non_productive, unused_token, duplicate_rule or typo",
+            ), //tested
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 enum ActionDetail {
     Shift(StateId),
@@ -153,6 +244,7 @@ enum ActionDetail {
     RRConflict(RuleId),  //Was a reduce, but part of a conflict
     SHResolved(StateId), //Was a shift. Associativity resolved conflict
     RDResolved(RuleId),  //Was reduce. Associativity resolved conflict
+    ShiftReduce(RuleId), //Shift to a state that can only reduce this rule, and reduce it at once
     NotUsed,             //Deleted by compression
 }
 
@@ -241,6 +333,8 @@ pub struct Pomelo {
     token_enum: Option<ItemEnum>, //The enum Token{}, if specified with %token
     parser_struct: Option<ItemStruct>, //The struct Parser{}, if specified with %parser
     states: Vec<StateId>,         //Table of states sorted by state number
+    //States by the (rule, dot) of their basis
+    state_index: HashMap<Vec<(usize, usize)>, Vec<StateId>>,
     rules: Vec<RuleId>,           //List of all rules
     default_index: usize,         //The index of the default symbol (always the last one in symbols)
     num_terminals: usize,         //symbols[0..num_terminals] are the terminal symbols
@@ -250,14 +344,50 @@ pub struct Pomelo {
     arg: Option<Type>,            //Declaration of the extra argument to parser
     err_type: Option<Type>,       //Declaration of the error type of the parser
     nconflict: i32,               //Number of parsing conflicts
+    nconflict_rr: i32,            //How many of them are reduce/reduce conflicts
+    expect: Option<usize>,        //Expected number of shift/reduce conflicts (%expect)
+    expect_rr: Option<usize>,     //Expected number of reduce/reduce conflicts (%expect_rr)
     has_fallback: bool,           //True if any %fallback is seen in the grammar
     default_type: Option<Type>,   //The %default_type
     start: Option<SymbolId>,
-    optional_tokens: HashMap<SymbolId, SymbolSpan>,
+    //Hidden non-terminals for `X?`, `X*`, `X % S`, `(x Y)`...
+    hidden_symbols: HashMap<Hidden, SymbolSpan>,
+    alt_enums: BTreeSet<usize>,   //Number of variants of the `AltN` enums used by groups
+    //Parameterized non-terminals
+    templates: BTreeMap<String, Template>,
+    //Instances to be expanded, with their depth
+    instances: Vec<(Ident, SymbolSpan, Vec<SymbolId>, usize, Location)>,
+    //Values of the parameters while expanding an instance
+    bindings: HashMap<String, SymbolId>,
+    //Mid-rule actions, with their hidden non-terminal
+    mid_actions: Vec<(SymbolSpan, Option<Type>, Block)>,
+    expansion_depth: usize,
     extra_token: Option<Type>,
     stack_type: Option<Type>,
     stack_limit: usize,
+    stack_auto: bool,             //True for `%stack_size auto`, limited to the maximum depth
+    //The maximum number of entries in the stack, None if unbounded
+    max_stack_depth: Option<usize>,
     verbose: bool,
+    //The %destructor of each symbol
+    destructors: Vec<(Ident, Option<Pat>, Block)>,
+    //The %token_destructor, for tokens without a %destructor
+    token_destructor: Option<Block>,
+    //Additional start symbols, with their augmented `S'`
+    extra_starts: Vec<(Ident, SymbolId)>,
+    //The start state of each additional start symbol
+    extra_start_states: Vec<StateId>,
+    inline: Vec<SymbolSpan>,      //The %inline non-terminals, substituted where used
+    algorithm: Option<Algorithm>, //The %algorithm used to build the states, LALR(1) by default
+    tables: Option<Tables>,       //The format of the %tables, compressed by default
+    glr: bool,                    //True with %glr, for a GLR parser
+    //The %merge of each symbol, for ambiguities with %glr
+    merges: Vec<(Ident, Pat, Pat, Block)>,
+    lints: Vec<Lint>,             //The lints checked, see %deny and %allow
+    strict: bool,                 //True if %strict is seen, every symbol must be declared
+    //Symbols declared with %terminal (true) or %nonterminal (false)
+    declared: HashMap<String, bool>,
+    location: Location,           //The file location of the declaration being parsed
 }
 
 struct ParserData {
@@ -502,10 +632,69 @@ fn error<T, M: Display>(msg: M) -> syn::Result<T> {
     Err(syn::Error::new(Span::call_site(), msg))
 }
 
+//Join the non-empty words with a space
+fn join_words(words: &[&str]) -> String {
+    words
+        .iter()
+        .filter(|w| !w.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 fn error_span<T>(span: Span, msg: &'static str) -> syn::Result<T> {
     Err(syn::Error::new(span, msg))
 }
 
+//The tokens of an included file all have the span of the directive, so its location in the
+//file is told in the message
+pub fn add_location(location: &Location, error: syn::Error) -> syn::Error {
+    let location = match location {
+        Some(location) => location,
+        None => return error,
+    };
+    let mut errors = error
+        .into_iter()
+        .map(|e| syn::Error::new(e.span(), format!("{}: {}", location, e)));
+    let mut res = errors.next().unwrap();
+    errors.for_each(|e| res.combine(e));
+    res
+}
+
+fn error_at<T>(location: &Location, span: Span, msg: &'static str) -> syn::Result<T> {
+    Err(add_location(location, syn::Error::new(span, msg)))
+}
+
+//True if `b` is `a` with one character inserted, removed, replaced or swapped with the next one
+fn one_edit_away(a: &str, b: &str) -> bool {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let (short, long) = if a.len() <= b.len() {
+        (&a, &b)
+    } else {
+        (&b, &a)
+    };
+    let prefix = short
+        .iter()
+        .zip(long.iter())
+        .take_while(|(x, y)| x == y)
+        .count();
+    match long.len() - short.len() {
+        0 => {
+            if prefix == short.len() {
+                return false;
+            }
+            short[prefix + 1..] == long[prefix + 1..]
+                || (prefix + 1 < short.len()
+                    && short[prefix] == long[prefix + 1]
+                    && short[prefix + 1] == long[prefix]
+                    && short[prefix + 2..] == long[prefix + 2..])
+        }
+        1 => short[prefix..] == long[prefix + 1..],
+        _ => false,
+    }
+}
+
 fn is_terminal_ident(id: &Ident) -> bool {
     id.to_string().chars().next().unwrap().is_ascii_uppercase()
 }
@@ -514,8 +703,26 @@ fn is_nonterminal_ident(id: &Ident) -> bool {
     id.to_string().chars().next().unwrap().is_ascii_lowercase()
 }
 
+//Replaces every identifier in `ts` that is a key in `values` with its value
+fn replace_idents(ts: TokenStream, values: &HashMap<String, TokenStream>) -> TokenStream {
+    ts.into_iter()
+        .map(|tt| match tt {
+            TokenTree::Ident(id) => match values.get(&id.to_string()) {
+                Some(value) => TokenTree::Group(Group::new(Delimiter::None, value.clone())),
+                None => TokenTree::Ident(id),
+            },
+            TokenTree::Group(g) => {
+                let mut ng = Group::new(g.delimiter(), replace_idents(g.stream(), values));
+                ng.set_span(g.span());
+                TokenTree::Group(ng)
+            }
+            tt => tt,
+        })
+        .collect()
+}
+
 impl Pomelo {
-    pub fn new_from_decls(decls: Vec<Decl>) -> syn::Result<Pomelo> {
+    pub fn new_from_decls(decls: Vec<(Decl, Location)>) -> syn::Result<Pomelo> {
         let mut pomelo = Pomelo {
             the_symbols: VecRef::new(),
             the_states: VecRef::new(),
@@ -526,9 +733,23 @@ impl Pomelo {
             syntax_error: None,
             parse_fail: None,
             stack_overflow: None,
+            destructors: Vec::new(),
+            token_destructor: None,
+            extra_starts: Vec::new(),
+            extra_start_states: Vec::new(),
+            inline: Vec::new(),
+            algorithm: None,
+            tables: None,
+            glr: false,
+            merges: Vec::new(),
+            lints: vec![Lint::NonProductive, Lint::DuplicateRule, Lint::Typo],
+            strict: false,
+            declared: HashMap::new(),
+            location: None,
             token_enum: None,
             parser_struct: None,
             states: Vec::new(),
+            state_index: HashMap::new(),
             rules: Vec::new(),
             default_index: 0,
             num_terminals: 0,
@@ -538,13 +759,24 @@ impl Pomelo {
             arg: None,
             err_type: None,
             nconflict: 0,
+            nconflict_rr: 0,
+            expect: None,
+            expect_rr: None,
             has_fallback: false,
             default_type: None,
             start: None,
-            optional_tokens: HashMap::new(),
+            hidden_symbols: HashMap::new(),
+            alt_enums: BTreeSet::new(),
+            templates: BTreeMap::new(),
+            instances: Vec::new(),
+            bindings: HashMap::new(),
+            mid_actions: Vec::new(),
+            expansion_depth: 0,
             extra_token: None,
             stack_type: None,
             stack_limit: 100,
+            stack_auto: false,
+            max_stack_depth: None,
             verbose: false,
         };
 
@@ -553,9 +785,21 @@ impl Pomelo {
 
         let mut pdata = ParserData { precedence: 0 };
 
-        for decl in decls {
-            pomelo.parse_one_decl(&mut pdata, decl)?;
+        //The kind of every declared symbol must be known before it is used
+        let (kinds, decls): (Vec<_>, Vec<_>) = decls.into_iter().partition(|(decl, _)| {
+            matches!(
+                decl,
+                Decl::Strict | Decl::Terminal(_) | Decl::NonTerminal(_)
+            )
+        });
+        for (decl, location) in kinds.into_iter().chain(decls) {
+            pomelo.location = location;
+            pomelo
+                .parse_one_decl(&mut pdata, decl)
+                .map_err(|e| add_location(&pomelo.location, e))?;
         }
+        pomelo.expand_templates()?;
+        pomelo.location = None;
 
         pomelo.symbol_new("{default}", NewSymbolType::NonTerminal);
 
@@ -570,20 +814,76 @@ impl Pomelo {
     }
     pub fn build(&mut self) -> syn::Result<TokenStream> {
         self.prepare();
-        self.find_rule_precedences();
         self.normalize_rules()?;
+        self.inline_rules()?;
+        self.lint_grammar()?;
+        self.find_rule_precedences();
         self.find_first_sets();
         self.find_states()?;
-        self.find_links();
-        self.find_follow_sets();
+        //With LR(1) the follow sets are computed as the states are built
+        if !self.is_lr1() {
+            self.find_links();
+            self.find_follow_sets();
+        }
         self.find_actions()?;
 
-        if self.verbose || self.nconflict > 0 {
-            let report = self.report_output();
-            if self.nconflict > 0 {
-                return error(format!("Parsing conflicts:\n {}", report));
+        //Conflicts are an error, unless there are exactly as many as expected.
+        //With %glr they are expected, unless the number is given explicitly.
+        let nconflict_sr = (self.nconflict - self.nconflict_rr) as usize;
+        let nconflict_rr = self.nconflict_rr as usize;
+        let expected = (self.expect.unwrap_or(0), self.expect_rr.unwrap_or(0));
+        let unexpected = (nconflict_sr, nconflict_rr) != expected
+            && (!self.glr || self.expect.is_some() || self.expect_rr.is_some());
+        if self.verbose || unexpected {
+            //The full report of the states is too long for an error, explain each conflict instead
+            let conflicts = self.report_conflicts();
+            if unexpected {
+                let mut errors = if self.expect.is_none() && self.expect_rr.is_none() {
+                    None
+                } else {
+                    Some(syn::Error::new(
+                        Span::call_site(),
+                        format!(
+                            "Expected {} shift/reduce and {} reduce/reduce conflicts, found {} and {}",
+                            expected.0, expected.1, nconflict_sr, nconflict_rr
+                        ),
+                    )) //tested
+                };
+                for conflict in conflicts {
+                    match &mut errors {
+                        Some(errors) => errors.combine(conflict),
+                        None => errors = Some(conflict),
+                    }
+                }
+                return Err(errors.unwrap()); //tested
+            }
+            println!("{}", self.report_output());
+            for conflict in conflicts.into_iter().flatten() {
+                println!("{}", conflict);
+            }
+        }
+
+        self.max_stack_depth = self.find_max_stack_depth();
+        if self.verbose {
+            println!("{}", self.report_stack_depth());
+        }
+        if self.stack_auto {
+            match self.max_stack_depth {
+                Some(depth) => self.stack_limit = depth,
+                None => {
+                    let mut errors = self.unbounded_rules().into_iter().map(|rp| {
+                        syn::Error::new(
+                            self.the_rules.get(rp).span,
+                            "%stack_size auto needs a bounded stack, but this rule can nest without limit",
+                        )
+                    });
+                    let mut error = errors.next().unwrap_or_else(|| {
+                        syn::Error::new(Span::call_site(), "%stack_size auto needs a bounded stack")
+                    }); //tested
+                    errors.for_each(|e| error.combine(e));
+                    return Err(error);
+                }
             }
-            println!("{}", report);
         }
 
         self.compress_tables();
@@ -644,38 +944,527 @@ impl Pomelo {
             }
         }
 
-        //For every optional token T of type ty (a new non-terminal _t of type Option<ty> has already been created),
+        //Hidden non-terminals can be nested, so compute their types first, the inner ones before
+        //They are taken in the order of their symbols, so that the new rules are always numbered
+        //the same
+        let hidden_symbols = std::mem::take(&mut self.hidden_symbols);
+        let mut hidden_list = hidden_symbols.iter().collect::<Vec<_>>();
+        hidden_list.sort_by_key(|(_, SymbolSpan(sym_l, _))| self.the_symbols.get(sym_l).index);
+        let mut pending = hidden_list
+            .iter()
+            .map(|(hidden, sym_l)| (*hidden, sym_l.0))
+            .collect::<Vec<_>>();
+        while !pending.is_empty() {
+            let pending_syms = pending.iter().map(|(_, s)| *s).collect::<Vec<_>>();
+            let (ready, rest): (Vec<_>, Vec<_>) = pending.into_iter().partition(|(hidden, _)| {
+                hidden
+                    .value_symbols()
+                    .iter()
+                    .all(|s| !pending_syms.contains(s))
+            });
+            for (hidden, sym_l) in ready {
+                let dt = self.hidden_data_type(hidden);
+                self.the_symbols.get_mut(sym_l).data_type = dt;
+            }
+            pending = rest;
+        }
+
+        //For every optional token T of type ty (a new non-terminal _n of type Option<ty> has already been created),
         //add two new rules:
-        //  _n ::= T { None }
+        //  _n ::= { None }
         //  _n ::= T(_A) { Some(_A) }
-        //We consume the optional_tokens map, it is no longer needed
-        let optional_tokens = std::mem::replace(&mut self.optional_tokens, Default::default());
-        for (sym_r, SymbolSpan(sym_l, span)) in optional_tokens {
-            let dt = self
+        //For every repeated token T* or T+ (a new non-terminal _n of type Vec<ty> has already been created),
+        //add two new left-recursive rules:
+        //  _n ::= { Vec::new() }         or      _n ::= T(_A) { vec![_A] }
+        //  _n ::= _n(mut _L) T(_A) { _L.push(_A); _L }
+        //For every separated list T+ % S the second rule is instead:
+        //  _n ::= _n(mut _L) S T(_A) { _L.push(_A); _L }
+        //Other separated lists (T* % S, T+ % S?, T* % S?) are built upon the hidden T+ % S (_p):
+        //  _n ::= { Vec::new() }         (if T*)
+        //  _n ::= _p(_L) { _L }
+        //  _n ::= _p(_L) S { _L }        (if S?)
+        //For every group (a B | c) add a rule for each alternative. Its value is a tuple of the values
+        //of the symbols with type (or the single value, or `()`), and if the alternatives have
+        //different types, it is wrapped in an `AltN` enum:
+        //  _n ::= a(_A0) B(_A1) { AltN::A((_A0, _A1)) }
+        //  _n ::= c(_A0) { AltN::B(_A0) }
+        //We consume the hidden_symbols map, it is no longer needed
+        for (hidden, SymbolSpan(sym_l, span)) in hidden_list {
+            let span = *span;
+            let sym_l = SymbolSpan(*sym_l, span);
+
+            let (sym_r, quantifier, separator) = match hidden {
+                Hidden::Ebnf(sym_r, quantifier, separator) => (*sym_r, *quantifier, *separator),
+                Hidden::Group(alts) => {
+                    let alt_types = alts
+                        .iter()
+                        .map(|alt| self.alternative_type(alt))
+                        .collect::<Vec<_>>();
+                    let is_enum = alt_types.iter().any(|t| *t != alt_types[0]);
+                    for (i, alt) in alts.iter().enumerate() {
+                        let mut values = Vec::new();
+                        let rhs = alt
+                            .iter()
+                            .map(|s| {
+                                let alias = if self.symbol_value_type(*s).is_some() {
+                                    let a = Ident::new(
+                                        &format!("_A{}", values.len()),
+                                        Span::call_site(),
+                                    );
+                                    values.push(a.clone());
+                                    Some(parse_quote!(#a))
+                                } else {
+                                    None
+                                };
+                                SymbolAlias(*s, span, alias)
+                            })
+                            .collect();
+                        let mut value = match &values[..] {
+                            [v] => quote!(#v),
+                            vs => quote!((#(#vs),*)),
+                        };
+                        if is_enum {
+                            let alt_enum =
+                                Ident::new(&format!("Alt{}", alts.len()), Span::call_site());
+                            let variant = Ident::new(
+                                &((b'A' + i as u8) as char).to_string(),
+                                Span::call_site(),
+                            );
+                            value = quote!(#alt_enum::#variant(#value));
+                        }
+                        self.create_rule(
+                            span,
+                            sym_l.clone(),
+                            rhs,
+                            Some(parse_quote!({ #value })),
+                            None,
+                        );
+                    }
+                    continue;
+                }
+                //Already expanded
+                Hidden::Instance(..) => continue,
+            };
+
+            match (quantifier, separator) {
+                (Quantifier::Optional, _) => {
+                    self.create_rule(
+                        span,
+                        sym_l.clone(),
+                        vec![],
+                        Some(parse_quote!({ None })),
+                        None,
+                    );
+
+                    let rhs = SymbolAlias(sym_r, span, Some(parse_quote!(_A)));
+                    self.create_rule(
+                        span,
+                        sym_l,
+                        vec![rhs],
+                        Some(parse_quote!({ Some(_A) })),
+                        None,
+                    );
+                }
+                (Quantifier::ZeroOrMore, None)
+                | (Quantifier::OneOrMore, None)
+                | (Quantifier::OneOrMore, Some((_, false))) => {
+                    if let Quantifier::ZeroOrMore = quantifier {
+                        self.create_rule(
+                            span,
+                            sym_l.clone(),
+                            vec![],
+                            Some(parse_quote!({ std::vec::Vec::new() })),
+                            None,
+                        );
+                    } else {
+                        let rhs = SymbolAlias(sym_r, span, Some(parse_quote!(_A)));
+                        self.create_rule(
+                            span,
+                            sym_l.clone(),
+                            vec![rhs],
+                            Some(parse_quote!({ std::vec![_A] })),
+                            None,
+                        );
+                    }
+
+                    let mut rhs = vec![SymbolAlias(sym_l.0, span, Some(parse_quote!(mut _L)))];
+                    if let Some((sep, _)) = separator {
+                        rhs.push(SymbolAlias(sep, span, None));
+                    }
+                    rhs.push(SymbolAlias(sym_r, span, Some(parse_quote!(_A))));
+                    self.create_rule(
+                        span,
+                        sym_l,
+                        rhs,
+                        Some(parse_quote!({
+                            _L.push(_A);
+                            _L
+                        })),
+                        None,
+                    );
+                }
+                (_, Some((sep, trailing))) => {
+                    let base = &hidden_symbols
+                        [&Hidden::Ebnf(sym_r, Quantifier::OneOrMore, Some((sep, false)))];
+
+                    if let Quantifier::ZeroOrMore = quantifier {
+                        self.create_rule(
+                            span,
+                            sym_l.clone(),
+                            vec![],
+                            Some(parse_quote!({ std::vec::Vec::new() })),
+                            None,
+                        );
+                    }
+                    let rhs = SymbolAlias(base.0, span, Some(parse_quote!(_L)));
+                    self.create_rule(
+                        span,
+                        sym_l.clone(),
+                        vec![rhs.clone()],
+                        Some(parse_quote!({ _L })),
+                        None,
+                    );
+                    if trailing {
+                        let rhs = vec![rhs, SymbolAlias(sep, span, None)];
+                        self.create_rule(span, sym_l, rhs, Some(parse_quote!({ _L })), None);
+                    }
+                }
+                (Quantifier::One, _) => unreachable!("plain symbols are not hidden"),
+            }
+        }
+
+        //For every mid-rule action add an empty rule with its code:
+        //  @n ::= { code }
+        for (sym_l, _, code) in self.mid_actions.clone() {
+            self.create_rule(sym_l.1, sym_l, vec![], Some(code), None);
+        }
+
+        //For every additional start symbol S add the rule of its augmented non-terminal, with the
+        //same type:
+        //  S' ::= S(_A) { _A }
+        for (id, sym_l) in self.extra_starts.clone() {
+            let span = id.span();
+            let sym_r = self.symbol_find(&id.to_string()).unwrap();
+            let data_type = self.the_symbols.get(sym_r).data_type.clone();
+            self.the_symbols.get_mut(sym_l).data_type = data_type;
+            let rhs = if self.symbol_value_type(sym_r).is_some() {
+                SymbolAlias(sym_r, span, Some(parse_quote!(_A)))
+            } else {
+                SymbolAlias(sym_r, span, None)
+            };
+            let code = rhs.2.as_ref().map(|_| parse_quote!({ _A }));
+            self.create_rule(span, SymbolSpan(sym_l, span), vec![rhs], code, None);
+        }
+    }
+
+    //The declared data type of a symbol. MultiTerminals have the type of their first child.
+    fn symbol_data_type(&self, sp: SymbolId) -> Option<Type> {
+        let sp = self.the_symbols.get(sp);
+        match &sp.typ {
+            MultiTerminal(ss) => ss
+                .first()
+                .and_then(|s| self.the_symbols.get(s).data_type.clone()),
+            _ => sp.data_type.clone(),
+        }
+    }
+
+    //The type of the value of a symbol, as it will be in the stack, or None if it has no value
+    fn symbol_value_type(&self, sp: SymbolId) -> Option<Type> {
+        if Some(sp) == self.wildcard {
+            return None;
+        }
+        self.symbol_data_type(sp)
+            .or_else(|| self.default_type.clone())
+    }
+
+    //The type of the value of an alternative in a group: a tuple of the values of the symbols
+    fn alternative_type(&self, alt: &[SymbolId]) -> Type {
+        let types = alt
+            .iter()
+            .filter_map(|s| self.symbol_value_type(*s))
+            .collect::<Vec<_>>();
+        match &types[..] {
+            [t] => t.clone(),
+            ts => parse_quote!((#(#ts),*)),
+        }
+    }
+
+    fn hidden_data_type(&mut self, hidden: &Hidden) -> Option<Type> {
+        let dt = match hidden {
+            Hidden::Ebnf(sym_r, quantifier, _) => {
+                let dt = self
+                    .symbol_value_type(*sym_r)
+                    .unwrap_or_else(|| parse_quote!(()));
+                match quantifier {
+                    Quantifier::Optional => parse_quote!(Option<#dt>),
+                    _ => parse_quote!(std::vec::Vec<#dt>),
+                }
+            }
+            Hidden::Group(alts) => {
+                let alt_types = alts
+                    .iter()
+                    .map(|alt| self.alternative_type(alt))
+                    .collect::<Vec<_>>();
+                if alt_types.iter().all(|t| *t == alt_types[0]) {
+                    alt_types[0].clone()
+                } else {
+                    self.alt_enums.insert(alts.len());
+                    let alt_enum = Ident::new(&format!("Alt{}", alts.len()), Span::call_site());
+                    parse_quote!(#alt_enum<#(#alt_types),*>)
+                }
+            }
+            //The type of the template, with the parameters replaced by the types of the arguments
+            Hidden::Instance(template, args) => {
+                let (params, ty) = self.templates[template].data_type.as_ref()?;
+                let types = params
+                    .iter()
+                    .zip(args)
+                    .map(|(p, a)| {
+                        let dt = self
+                            .symbol_value_type(*a)
+                            .unwrap_or_else(|| parse_quote!(()));
+                        (p.to_string(), dt.into_token_stream())
+                    })
+                    .collect();
+                let ty = replace_idents(ty.into_token_stream(), &types);
+                parse_quote!(#ty)
+            }
+        };
+        Some(dt)
+    }
+
+    /* Look for mistakes in the grammar that are not caught by building the parser: non-terminals
+     ** that are unreachable or that never derive a string of tokens, tokens that are declared
+     ** but not used, duplicated rules and tokens that look like a typo of another one.
+     ** Each of them is checked only if enabled, by default or with %deny, and not disabled with
+     ** %allow.
+     */
+    fn lint_grammar(&self) -> syn::Result<()> {
+        if self.lints.is_empty() {
+            return Ok(());
+        }
+        let mut errors = Vec::new();
+
+        //Where each non-terminal is defined, that is, its first rule
+        let defined = |sp: SymbolId| {
+            let sp = self.the_symbols.get(sp);
+            sp.get_non_terminal_rules()
+                .and_then(|rules| rules.first().copied())
+                .map(|rp| {
+                    let rule = self.the_rules.get(rp);
+                    (rule.lhs.1, rule.location.clone())
+                })
+        };
+
+        //Reachable from any of the start symbols
+        let mut reachable = HashSet::new();
+        let mut pending = vec![self.start.unwrap()];
+        pending.extend(self.extra_starts.iter().map(|(_, sym_l)| *sym_l));
+        while let Some(sp) = pending.pop() {
+            if !reachable.insert(sp) {
+                continue;
+            }
+            for rp in self
                 .the_symbols
-                .get(sym_r)
-                .data_type
-                .clone()
-                .unwrap_or_else(|| parse_quote!(()));
-            self.the_symbols.get_mut(sym_l).data_type = Some(parse_quote!(Option<#dt>));
-            let sym_l = SymbolSpan(sym_l, span);
-
-            self.create_rule(
-                span,
-                sym_l.clone(),
-                vec![],
-                Some(parse_quote!({ None })),
-                None,
-            );
+                .get(sp)
+                .get_non_terminal_rules()
+                .unwrap_or(&[])
+            {
+                let rule = self.the_rules.get(rp);
+                pending.extend(rule.rhs.iter().map(|SymbolAlias(sp, ..)| *sp));
+            }
+        }
+        for &sp in &self.symbols {
+            if reachable.contains(&sp) {
+                continue;
+            }
+            if let Some((span, location)) = defined(sp) {
+                errors.push((
+                    Lint::Unreachable,
+                    add_location(
+                        &location,
+                        syn::Error::new(
+                            span,
+                            "Non-terminal is not reachable from the start symbol",
+                        ),
+                    ),
+                )); //tested
+            }
+        }
+
+        //Productive if any rule has only tokens and productive symbols.
+        //Non-terminals without rules are reported elsewhere, so they count as productive here.
+        let mut productive = self
+            .symbols
+            .iter()
+            .copied()
+            .filter(|&sp| defined(sp).is_none())
+            .collect::<HashSet<_>>();
+        loop {
+            let mut progress = false;
+            for rp in &self.rules {
+                let rule = self.the_rules.get(rp);
+                if !productive.contains(&rule.lhs.0)
+                    && rule
+                        .rhs
+                        .iter()
+                        .all(|SymbolAlias(sp, ..)| productive.contains(sp))
+                {
+                    productive.insert(rule.lhs.0);
+                    progress = true;
+                }
+            }
+            if !progress {
+                break;
+            }
+        }
+        for &sp in &self.symbols {
+            if productive.contains(&sp) || !reachable.contains(&sp) {
+                continue;
+            }
+            if let Some((span, location)) = defined(sp) {
+                errors.push((
+                    Lint::NonProductive,
+                    add_location(
+                        &location,
+                        syn::Error::new(
+                            span,
+                            "Non-terminal never derives a sequence of tokens, it recurses forever",
+                        ),
+                    ),
+                )); //tested
+            }
+        }
+
+        //How many times each token is used in the rules, and where
+        let mut uses = HashMap::new();
+        let mut in_class = HashSet::new();
+        for rp in &self.rules {
+            let rule = self.the_rules.get(rp);
+            for SymbolAlias(sp, span, _) in &rule.rhs {
+                uses.entry(*sp)
+                    .or_insert((0, *span, rule.location.clone()))
+                    .0 += 1;
+            }
+            if let Some(sp) = rule.prec_sym {
+                uses.entry(sp)
+                    .or_insert((0, rule.span, rule.location.clone()))
+                    .0 += 1;
+            }
+        }
+        let mut fallbacks = HashSet::new();
+        for &sp in &self.symbols {
+            let s = self.the_symbols.get(sp);
+            if let MultiTerminal(sub_sym) = &s.typ {
+                in_class.extend(sub_sym.iter().copied());
+            }
+            fallbacks.extend(s.fallback);
+        }
+        for &sp in &self.symbols {
+            let s = self.the_symbols.get(sp);
+            let span = match (&s.typ, s.decl_span) {
+                (Terminal, Some(span)) => span,
+                _ => continue,
+            };
+            //Fallback tokens, tokens in a class and the wildcard are used through other tokens.
+            //Tokens with attributes are there for the Token enum, maybe for a lexer, and so are
+            //tokens declared without a type, as a Lemon `%token`.
+            if (s.data_type.is_none() && s.precedence.is_none())
+                || uses.contains_key(&sp)
+                || s.fallback.is_some()
+                || fallbacks.contains(&sp)
+                || in_class.contains(&sp)
+                || Some(sp) == self.wildcard
+                || !s.attributes.is_empty()
+            {
+                continue;
+            }
+            errors.push((
+                Lint::UnusedToken,
+                add_location(
+                    &s.decl_location,
+                    syn::Error::new(
+                        span,
+                        format!("Token {} is declared but not used in any rule", s.name),
+                    ),
+                ),
+            )); //tested
+        }
 
-            let rhs = SymbolAlias(sym_r, span, Some(parse_quote!(_A)));
-            self.create_rule(
-                span,
-                sym_l,
-                vec![rhs],
-                Some(parse_quote!({ Some(_A) })),
-                None,
+        //The same rule twice would be a reduce/reduce conflict
+        let mut seen = HashSet::new();
+        for rp in &self.rules {
+            let rule = self.the_rules.get(rp);
+            let key = (
+                rule.lhs.0,
+                rule.rhs
+                    .iter()
+                    .map(|SymbolAlias(sp, ..)| *sp)
+                    .collect::<Vec<_>>(),
             );
+            if !seen.insert(key) {
+                errors.push((
+                    Lint::DuplicateRule,
+                    add_location(
+                        &rule.location,
+                        syn::Error::new(rule.span, "Duplicated rule"),
+                    ),
+                ));
+                //tested
+            }
+        }
+
+        //A token that is used only once and is not declared anywhere may be a typo of another one.
+        //Short names are too alike to tell.
+        for &sp in &self.symbols {
+            let s = self.the_symbols.get(sp);
+            let (span, location) = match (&s.typ, uses.get(&sp)) {
+                (Terminal, Some((1, span, location))) => (*span, location),
+                _ => continue,
+            };
+            if s.decl_span.is_some()
+                || s.fallback.is_some()
+                || in_class.contains(&sp)
+                || Some(sp) == self.wildcard
+                || s.name.chars().count() < 4
+            {
+                continue;
+            }
+            let similar = self.symbols.iter().find(|&&other| {
+                let o = self.the_symbols.get(other);
+                let used = uses.get(&other).map_or(0, |u| u.0);
+                !matches!(o.typ, NonTerminal { .. })
+                    && (used > 1 || o.decl_span.is_some())
+                    && one_edit_away(&s.name, &o.name)
+            });
+            if let Some(&other) = similar {
+                errors.push((
+                    Lint::Typo,
+                    add_location(
+                        location,
+                        syn::Error::new(
+                            span,
+                            format!(
+                                "Token {} is used only once, did you mean {}?",
+                                s.name,
+                                self.the_symbols.get(other).name
+                            ),
+                        ),
+                    ),
+                )); //tested
+            }
+        }
+
+        let mut errors = errors
+            .into_iter()
+            .filter(|(lint, _)| self.lints.contains(lint))
+            .map(|(_, error)| error);
+        match errors.next() {
+            None => Ok(()),
+            Some(mut error) => {
+                error.extend(errors);
+                Err(error)
+            }
         }
     }
 
@@ -743,7 +1532,8 @@ impl Pomelo {
                         continue;
                     }
                 }
-                return error_span(
+                return error_at(
+                    &rp.location,
                     rp.lhs.1,
                     "This rule has a typed LHS but no code to assign it",
                 ); //tested
@@ -752,6 +1542,134 @@ impl Pomelo {
         Ok(())
     }
 
+    //Substitute every %inline non-terminal I by each of its productions, in every rule that uses it.
+    //The value of I is computed by the code of the production, with its aliases renamed, and then
+    //bound to the alias of I in the outer rule:
+    //  expr ::= expr(a) binop(op) expr(b) { op(a, b) }
+    //  binop ::= Plus(p) { p.add() }
+    //becomes:
+    //  expr ::= expr(a) Plus(_I0) expr(b) { let op = { let p = _I0; { p.add() } }; { op(a, b) } }
+    //Since Plus is now in the outer rule, its precedence applies to it.
+    fn inline_rules(&mut self) -> syn::Result<()> {
+        if self.inline.is_empty() {
+            return Ok(());
+        }
+        let inline = self.inline.iter().map(|s| s.0).collect::<Vec<_>>();
+
+        for SymbolSpan(sp, span) in &self.inline {
+            if Some(*sp) == self.start
+                || self
+                    .extra_starts
+                    .iter()
+                    .any(|(id, _)| self.symbol_find(&id.to_string()) == Some(*sp))
+            {
+                return error_span(*span, "Start symbol cannot be inline");
+            }
+            let rules = self
+                .the_symbols
+                .get(sp)
+                .get_non_terminal_rules()
+                .unwrap()
+                .to_owned();
+            if rules.is_empty() {
+                return error_span(*span, "Inline non-terminal has no rules");
+            }
+            //Look for cycles, through other inline non-terminals
+            let mut pending = rules;
+            let mut seen = vec![*sp];
+            while let Some(rp) = pending.pop() {
+                for SymbolAlias(r, ..) in &self.the_rules.get(rp).rhs {
+                    if r == sp {
+                        return error_span(*span, "Inline non-terminal cannot be recursive");
+                        //tested
+                    }
+                    if inline.contains(r) && !seen.contains(r) {
+                        seen.push(*r);
+                        let rs = self
+                            .the_symbols
+                            .get(r)
+                            .get_non_terminal_rules()
+                            .unwrap()
+                            .to_owned();
+                        pending.extend(rs);
+                    }
+                }
+            }
+        }
+
+        //The rules are created again from scratch, so they are numbered correctly
+        let rules = std::mem::take(&mut self.rules);
+        let mut productions = HashMap::new();
+        for sp in &self.symbols {
+            if let NonTerminal { rules, .. } = &mut self.the_symbols.get_mut(sp).typ {
+                let rules = std::mem::take(rules);
+                if inline.contains(sp) {
+                    productions.insert(*sp, rules);
+                }
+            }
+        }
+
+        let mut num_alias = 0;
+        for rp in rules {
+            let (span, lhs, rhs, code, prec_sym) = {
+                let rp = self.the_rules.get(rp);
+                if inline.contains(&rp.lhs.0) {
+                    continue;
+                }
+                self.location = rp.location.clone();
+                (
+                    rp.span,
+                    rp.lhs.clone(),
+                    rp.rhs.clone(),
+                    rp.code.clone(),
+                    rp.prec_sym,
+                )
+            };
+            //A stack of partially expanded rules, pushed in reverse to keep the order of the productions
+            let mut pending = vec![(rhs, code, prec_sym)];
+            while let Some((rhs, code, prec_sym)) = pending.pop() {
+                let k = match rhs.iter().position(|SymbolAlias(s, ..)| inline.contains(s)) {
+                    Some(k) => k,
+                    None => {
+                        self.create_rule(span, lhs.clone(), rhs, code, prec_sym);
+                        continue;
+                    }
+                };
+                let SymbolAlias(sym, _, alias) = &rhs[k];
+                let alias = alias.clone().unwrap_or_else(|| parse_quote!(_));
+                let ty = self
+                    .symbol_value_type(*sym)
+                    .unwrap_or_else(|| parse_quote!(()));
+                for ip in productions[sym].iter().rev() {
+                    let ip = self.the_rules.get(ip);
+                    let mut inner = Vec::new();
+                    let mut bindings = Vec::new();
+                    for SymbolAlias(r, r_span, r_alias) in &ip.rhs {
+                        let r_alias = r_alias.as_ref().map(|a| {
+                            let fresh = Ident::new(&format!("_I{}", num_alias), Span::call_site());
+                            num_alias += 1;
+                            bindings.push(quote!(let #a = #fresh;));
+                            parse_quote!(#fresh)
+                        });
+                        inner.push(SymbolAlias(*r, *r_span, r_alias));
+                    }
+                    let inner_code = ip.code.as_ref().map(|c| quote!(#c)).unwrap_or_default();
+                    let outer_code = code.as_ref().map(|c| quote!(#c)).unwrap_or_default();
+                    let new_code = parse_quote!({
+                        let #alias: #ty = { #(#bindings)* #inner_code };
+                        #outer_code
+                    });
+                    let mut new_rhs = rhs[..k].to_vec();
+                    new_rhs.extend(inner);
+                    new_rhs.extend_from_slice(&rhs[k + 1..]);
+                    pending.push((new_rhs, Some(new_code), prec_sym.or(ip.prec_sym)));
+                }
+            }
+        }
+        self.location = None;
+        Ok(())
+    }
+
     /* Find all nonterminals which will generate the empty string.
      ** Then go back and compute the first sets of every nonterminal.
      ** The first set is the set of all terminal symbols which can begin
@@ -825,9 +1743,7 @@ impl Pomelo {
                                 lambda: b2_lambda,
                                 ..
                             } => {
-                                let n1 = s1_first_set.len();
-                                s1_first_set.extend(s2_first_set);
-                                progress |= s1_first_set.len() > n1;
+                                progress |= s1_first_set.union_with(s2_first_set);
                                 if !b2_lambda {
                                     break;
                                 }
@@ -854,10 +1770,10 @@ impl Pomelo {
          ** any rule.  Report an error if it does.  (YACC would generate a new
          ** start symbol in this case.) */
         for rp in &self.rules {
-            let rp = self.the_rules.get_mut(rp);
+            let rp = self.the_rules.get(rp);
             for SymbolAlias(r, span, ..) in &rp.rhs {
                 if sp == r {
-                    return error_span(*span, "start symbol on the RHS of a rule");
+                    return error_at(&rp.location, *span, "start symbol on the RHS of a rule");
                     //tested
                 }
             }
@@ -883,36 +1799,263 @@ impl Pomelo {
         }
         self.get_state(basis.clone(), basis)?;
 
+        /* Each additional start symbol S has its own start state, whose basis
+         ** is the single rule S' ::= S */
+        for (_, sp) in self.extra_starts.clone() {
+            let mut basis = Vec::new();
+            let rules = self
+                .the_symbols
+                .get(sp)
+                .get_non_terminal_rules()
+                .unwrap()
+                .to_owned();
+            for rp in rules {
+                self.the_rules.get_mut(rp).lhs_start = true;
+
+                let cfg = self.add_config(&mut basis, rp, 0);
+                self.the_configs.get_mut(cfg).fws.insert(0);
+            }
+            let stp = self.get_state(basis.clone(), basis)?;
+            self.extra_start_states.push(stp);
+        }
+
+        if self.algorithm == Some(Algorithm::Minimal) {
+            self.merge_states();
+        }
         Ok(())
     }
 
-    /* Compute the first state.  All other states will be
-     ** computed automatically during the computation of the first one.
-     ** The returned pointer to the first state is not used. */
-    fn get_state(&mut self, mut bp: Vec<ConfigId>, cur: Vec<ConfigId>) -> syn::Result<StateId> {
-        bp.sort_by(|a, b| config_cmp(self, *a, *b));
-        /* Get a state with the same basis */
-        match self.state_find(&bp) {
-            Some(stp) => {
-                /* A state with the same basis already exists!  Copy all the follow-set
-                 ** propagation links from the state under construction into the
-                 ** preexisting state, then return a pointer to the preexisting state */
-                let bstp = self.the_states.get(stp);
-                for (x, y) in bp.into_iter().zip(&bstp.basis) {
-                    let mut y = self.the_configs.get_mut(y);
-                    y.bplp.extend(&self.the_configs.get(x).bplp);
+    fn is_lr1(&self) -> bool {
+        matches!(
+            self.algorithm,
+            Some(Algorithm::Lr1) | Some(Algorithm::Minimal)
+        )
+    }
+
+    //With LR(1), propagate the follow sets inside a newly built state, so that they are complete
+    //before building its successors
+    fn closure_follow_sets(&mut self, configs: &[ConfigId]) {
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for cfp in configs {
+                let (fws, fplp) = {
+                    let cfp = self.the_configs.get(cfp);
+                    (cfp.fws.clone(), cfp.fplp.clone())
+                };
+                for plp in &fplp {
+                    let mut plp = self.the_configs.get_mut(plp);
+                    if plp.fws.union_with(&fws) {
+                        progress = true;
+                    }
                 }
-                Ok(stp)
             }
-            None => {
-                /* This really is a new state. Construct all the details */
-                let mut configs = self.configlist_closure(cur)?;
-                configs.sort_by(|a, b| config_cmp(self, *a, *b));
-                let stp = State {
-                    configs,
-                    basis: bp.clone(),
-                    state_num: self.states.len(),
-                    actions: Vec::new(),
+        }
+    }
+
+    //Merge the canonical LR(1) states with the same core, as LALR(1) would, except when the merged
+    //state would have a conflict that none of the original states had, in the style of Pager's
+    //minimal LR(1). Then split the groups until all the states in a group go to the same group with
+    //every symbol, checking again that each part can be merged.
+    fn merge_states(&mut self) {
+        let index = self
+            .states
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (*s, i))
+            .collect::<HashMap<_, _>>();
+
+        //For every state, the terminals that it shifts, and the follow sets of its complete rules
+        let mut cores = Vec::new();
+        let mut shifts = Vec::new();
+        let mut reduces = Vec::new();
+        for stp in &self.states {
+            let stp = self.the_states.get(stp);
+            let core = stp
+                .basis
+                .iter()
+                .map(|c| {
+                    let c = self.the_configs.get(c);
+                    (self.the_rules.get(c.rule).index, c.dot)
+                })
+                .collect::<Vec<_>>();
+            cores.push(core);
+            let shift = stp
+                .actions
+                .iter()
+                .map(|ap| self.the_symbols.get(ap.borrow().look_ahead).index)
+                .filter(|i| *i < self.num_terminals)
+                .collect::<RuleSet>();
+            shifts.push(shift);
+            let reduce = stp
+                .configs
+                .iter()
+                .map(|c| self.the_configs.get(c))
+                .filter(|c| c.dot == self.the_rules.get(c.rule).rhs.len())
+                .map(|c| (self.the_rules.get(c.rule).index, c.fws.clone()))
+                .collect::<Vec<_>>();
+            reduces.push(reduce);
+        }
+
+        //The actions of a group of states for a terminal: if it shifts, and the rules it reduces
+        let actions = |group: &[usize], t: usize| {
+            let rules = reduces[group[0]]
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| group.iter().any(|s| reduces[*s][*i].1.contains(t)))
+                .map(|(_, (rule, _))| *rule)
+                .collect::<Vec<_>>();
+            (shifts[group[0]].contains(t), rules)
+        };
+        let can_merge = |group: &[usize]| {
+            (0..self.num_terminals).all(|t| {
+                let merged = actions(group, t);
+                merged.0 as usize + merged.1.len() < 2
+                    || group.iter().any(|s| actions(&[*s], t) == merged)
+            })
+        };
+
+        //Each state goes to the first group where it can be merged
+        let partition = |states: Vec<usize>| {
+            let mut groups: Vec<Vec<usize>> = Vec::new();
+            for s in states {
+                let mut merged = false;
+                for g in &mut groups {
+                    g.push(s);
+                    if can_merge(g) {
+                        merged = true;
+                        break;
+                    }
+                    g.pop();
+                }
+                if !merged {
+                    groups.push(vec![s]);
+                }
+            }
+            groups
+        };
+
+        let mut core_groups: Vec<Vec<usize>> = Vec::new();
+        for (s, core) in cores.iter().enumerate() {
+            match core_groups.iter_mut().find(|g| cores[g[0]] == *core) {
+                Some(g) => g.push(s),
+                None => core_groups.push(vec![s]),
+            }
+        }
+        let mut groups = core_groups
+            .into_iter()
+            .flat_map(&partition)
+            .collect::<Vec<_>>();
+
+        //Split the groups whose states go to different groups. A part of a group may have a conflict
+        //that the whole group did not add, if the states that had it are in another part, so the
+        //parts are merged again.
+        let mut group_of = vec![0; self.states.len()];
+        loop {
+            for (g, group) in groups.iter().enumerate() {
+                for s in group {
+                    group_of[*s] = g;
+                }
+            }
+            let mut split = Vec::new();
+            for group in &groups {
+                let mut by_goto: Vec<(Vec<_>, Vec<usize>)> = Vec::new();
+                for s in group {
+                    let goto = self
+                        .the_states
+                        .get(self.states[*s])
+                        .actions
+                        .iter()
+                        .map(|ap| {
+                            let ap = ap.borrow();
+                            let target = match ap.detail {
+                                ActionDetail::Shift(t) => group_of[index[&t]],
+                                _ => unreachable!("only shifts are built with the states"),
+                            };
+                            (self.the_symbols.get(ap.look_ahead).index, target)
+                        })
+                        .collect::<Vec<_>>();
+                    match by_goto.iter_mut().find(|(g, _)| *g == goto) {
+                        Some((_, ss)) => ss.push(*s),
+                        None => by_goto.push((goto, vec![*s])),
+                    }
+                }
+                if by_goto.len() == 1 {
+                    split.push(group.clone());
+                } else {
+                    split.extend(by_goto.into_iter().flat_map(|(_, ss)| partition(ss)));
+                }
+            }
+            if split.len() == groups.len() {
+                break;
+            }
+            groups = split;
+        }
+
+        //The first state of each group takes the follow sets of the others. The order of the
+        //states is kept, so that the start state is still the first one.
+        groups.sort_by_key(|g| g[0]);
+        for (g, group) in groups.iter().enumerate() {
+            for s in group {
+                group_of[*s] = g;
+            }
+        }
+        let states = groups.iter().map(|g| self.states[g[0]]).collect::<Vec<_>>();
+        for (g, group) in groups.iter().enumerate() {
+            let stp = self.the_states.get(states[g]);
+            for s in &group[1..] {
+                let other = self.the_states.get(self.states[*s]);
+                for (a, b) in stp.configs.iter().zip(&other.configs) {
+                    let fws = self.the_configs.get(b).fws.clone();
+                    self.the_configs.get_mut(a).fws.union_with(&fws);
+                }
+            }
+            for ap in &stp.actions {
+                let mut ap = ap.borrow_mut();
+                if let ActionDetail::Shift(t) = ap.detail {
+                    ap.detail = ActionDetail::Shift(states[group_of[index[&t]]]);
+                }
+            }
+        }
+        for stp in &mut self.extra_start_states {
+            *stp = states[group_of[index[stp]]];
+        }
+        for (i, stp) in states.iter().enumerate() {
+            self.the_states.get_mut(stp).state_num = i;
+        }
+        self.states = states;
+    }
+
+    /* Compute the first state.  All other states will be
+     ** computed automatically during the computation of the first one.
+     ** The returned pointer to the first state is not used. */
+    fn get_state(&mut self, mut bp: Vec<ConfigId>, cur: Vec<ConfigId>) -> syn::Result<StateId> {
+        bp.sort_by(|a, b| config_cmp(self, *a, *b));
+        /* Get a state with the same basis */
+        match self.state_find(&bp) {
+            Some(stp) => {
+                /* A state with the same basis already exists!  Copy all the follow-set
+                 ** propagation links from the state under construction into the
+                 ** preexisting state, then return a pointer to the preexisting state */
+                let bstp = self.the_states.get(stp);
+                for (x, y) in bp.into_iter().zip(&bstp.basis) {
+                    let mut y = self.the_configs.get_mut(y);
+                    y.bplp.extend(&self.the_configs.get(x).bplp);
+                }
+                Ok(stp)
+            }
+            None => {
+                /* This really is a new state. Construct all the details */
+                let mut configs = self.configlist_closure(cur)?;
+                configs.sort_by(|a, b| config_cmp(self, *a, *b));
+                if self.is_lr1() {
+                    self.closure_follow_sets(&configs);
+                }
+                let stp = State {
+                    configs,
+                    basis: bp.clone(),
+                    state_num: self.states.len(),
+                    actions: Vec::new(),
                     n_tkn_act: 0,
                     n_nt_act: 0,
                     i_tkn_ofst: None,
@@ -921,6 +2064,8 @@ impl Pomelo {
                 };
                 let id = self.the_states.push(stp);
                 self.states.push(id);
+                let key = self.basis_key(&bp);
+                self.state_index.entry(key).or_default().push(id);
                 self.build_shifts(id)?;
                 Ok(id)
             }
@@ -974,6 +2119,11 @@ impl Pomelo {
                     dot = bcfp.dot + 1;
                 }
                 let newcfg = self.add_config(&mut basis, rule, dot);
+                if self.is_lr1() {
+                    //With LR(1) the lookaheads are part of the state, so they are needed now
+                    let fws = self.the_configs.get(bcfp_).fws.clone();
+                    self.the_configs.get_mut(newcfg).fws.union_with(&fws);
+                }
 
                 self.the_configs.get_mut(bcfp_).status = CfgStatus::Complete; /* Mark this config as used */
                 self.the_configs.get_mut(newcfg).bplp.push(bcfp_);
@@ -1058,9 +2208,7 @@ impl Pomelo {
                     };
                     for plp in &fplp {
                         let mut plp = self.the_configs.get_mut(plp);
-                        let n = plp.fws.len();
-                        plp.fws.extend(&fws);
-                        if plp.fws.len() > n {
+                        if plp.fws.union_with(&fws) {
                             plp.status = CfgStatus::Incomplete;
                             progress = true;
                         }
@@ -1086,7 +2234,7 @@ impl Pomelo {
                 if cfp.dot == self.the_rules.get(cfp.rule).rhs.len() {
                     /* Is dot at extreme right? */
                     for j in 0..self.num_terminals {
-                        if cfp.fws.contains(&j) {
+                        if cfp.fws.contains(j) {
                             /* Add a reduce action to the state "stp" which will reduce by the
                              ** rule "cfp->rp" if the lookahead symbol is "lemp->symbols[j]" */
                             let detail = ActionDetail::Reduce(cfp.rule);
@@ -1114,9 +2262,19 @@ impl Pomelo {
                 look_ahead: sp,
                 detail: ActionDetail::Accept,
             }));
+        for ((_, sp), stp) in self.extra_starts.iter().zip(&self.extra_start_states) {
+            self.the_states
+                .get_mut(stp)
+                .actions
+                .push(RefCell::new(Action {
+                    look_ahead: *sp,
+                    detail: ActionDetail::Accept,
+                }));
+        }
 
         /* Resolve conflicts */
         let mut nconflict = 0;
+        let mut nconflict_rr = 0;
         for stp in &self.states {
             self.the_states.get_mut(stp).actions.sort_by(|a, b| {
                 action_cmp(&self.the_symbols, &self.the_states, &self.the_rules, a, b)
@@ -1130,13 +2288,12 @@ impl Pomelo {
                     if ap.borrow().look_ahead == nap.borrow().look_ahead {
                         /* The two actions "ap" and "nap" have the same lookahead.
                          ** Figure out which one should be used */
-                        nconflict += if self
-                            .resolve_conflict(&mut *ap.borrow_mut(), &mut *nap.borrow_mut())
-                        {
-                            1
-                        } else {
-                            0
-                        };
+                        if self.resolve_conflict(&mut *ap.borrow_mut(), &mut *nap.borrow_mut()) {
+                            nconflict += 1;
+                            if let ActionDetail::RRConflict(_) = nap.borrow().detail {
+                                nconflict_rr += 1;
+                            }
+                        }
                     } else {
                         break;
                     }
@@ -1144,20 +2301,27 @@ impl Pomelo {
             }
         }
         self.nconflict += nconflict;
+        self.nconflict_rr += nconflict_rr;
 
         /* Report an error for each rule that can never be reduced. */
         for stp in &self.states {
             for a in &self.the_states.get(stp).actions {
                 let a = a.borrow();
-                if let ActionDetail::Reduce(x) = &a.detail {
-                    self.the_rules.get_mut(x).can_reduce = true;
+                match &a.detail {
+                    ActionDetail::Reduce(x) => self.the_rules.get_mut(x).can_reduce = true,
+                    //With %glr the conflicting reductions are done too
+                    ActionDetail::SRConflict(x) | ActionDetail::RRConflict(x) if self.glr => {
+                        self.the_rules.get_mut(x).can_reduce = true
+                    }
+                    _ => {}
                 }
             }
         }
         for rp in &self.rules {
             let rp = self.the_rules.get(rp);
             if !rp.can_reduce {
-                return error_span(rp.span, "This rule cannot be reduced"); //tested
+                return error_at(&rp.location, rp.span, "This rule cannot be reduced");
+                //tested
             }
         }
         Ok(())
@@ -1188,7 +2352,11 @@ impl Pomelo {
                 match (precx, precy) {
                     (Some(px), Some(py)) => match precedence_cmp(px, py) {
                         Ordering::Less => (false, SHResolved(x), Reduce(y)),
-                        Ordering::Equal => (false, Error, Reduce(y)),
+                        //With %precedence there is no associativity to solve this one
+                        Ordering::Equal => match px.1 {
+                            Associativity::Precedence => (true, Shift(x), SRConflict(y)),
+                            _ => (false, Error, Reduce(y)),
+                        },
                         Ordering::Greater => (false, Shift(x), RDResolved(y)),
                     },
                     _ => (true, Shift(x), SRConflict(y)),
@@ -1313,6 +2481,44 @@ impl Pomelo {
                 action_cmp(&self.the_symbols, &self.the_states, &self.the_rules, a, b)
             });
         }
+
+        /* A state that reduces a single rule, whatever the look-ahead, does not need to be
+         ** entered. Every shift into it becomes a SHIFTREDUCE, that shifts and reduces the rule
+         ** at once, and then the state is unused. Not with %glr, because the nodes of the graph
+         ** are merged by their states.
+         */
+        if self.glr {
+            return;
+        }
+        let mut auto_reduce = HashMap::new();
+        for stp in &self.states {
+            let actions = &self.the_states.get(stp).actions;
+            let rdflt = actions.iter().find_map(|ap| match ap.borrow().detail {
+                Reduce(rp) if ap.borrow().look_ahead == def_symbol => Some(rp),
+                _ => None,
+            });
+            if let Some(rdflt) = rdflt {
+                let only_reduce = actions.iter().all(|ap| match ap.borrow().detail {
+                    Reduce(rp) => rp == rdflt,
+                    NotUsed | SHResolved(_) | RDResolved(_) => true,
+                    _ => false,
+                });
+                if only_reduce {
+                    auto_reduce.insert(*stp, rdflt);
+                }
+            }
+        }
+        for stp in &self.states {
+            for ap in &self.the_states.get(stp).actions {
+                let mut ap = ap.borrow_mut();
+                if let Shift(next) = ap.detail {
+                    if let Some(rp) = auto_reduce.get(&next) {
+                        ap.detail = ShiftReduce(*rp);
+                    }
+                }
+            }
+        }
+        self.states.retain(|stp| !auto_reduce.contains_key(stp));
     }
 
     /*
@@ -1364,11 +2570,526 @@ impl Pomelo {
             Reduce(rp) => self.the_rules.get(rp).index + self.states.len(),
             Error => self.states.len() + self.rules.len(),
             Accept => self.states.len() + self.rules.len() + 1,
+            ShiftReduce(rp) => {
+                self.the_rules.get(rp).index + self.states.len() + self.rules.len() + 3
+            }
             _ => return None,
         };
         Some(act)
     }
 
+    /* Find the shortest sequence of symbols that leads to each state from a start state.
+     ** Return the previous state and the symbol of each step, start states are not in the map.
+     */
+    fn access_paths(&self) -> HashMap<StateId, (StateId, SymbolId)> {
+        let mut prev = HashMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(self.states[0]);
+        queue.extend(self.extra_start_states.iter().copied());
+        let starts = queue.clone();
+        while let Some(stp) = queue.pop_front() {
+            for ap in &self.the_states.get(stp).actions {
+                let ap = ap.borrow();
+                if let ActionDetail::Shift(next) = ap.detail {
+                    if !prev.contains_key(&next) && !starts.contains(&next) {
+                        prev.insert(next, (stp, ap.look_ahead));
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        prev
+    }
+
+    fn access_path(
+        &self,
+        prev: &HashMap<StateId, (StateId, SymbolId)>,
+        mut stp: StateId,
+    ) -> Vec<SymbolId> {
+        let mut path = Vec::new();
+        while let Some(&(from, sp)) = prev.get(&stp) {
+            path.push(sp);
+            stp = from;
+        }
+        path.reverse();
+        path
+    }
+
+    //Can the look-ahead `la` be the first terminal of `syms`?
+    fn starts_with(&self, syms: &[SymbolAlias], la: usize) -> bool {
+        for SymbolAlias(sp, ..) in syms {
+            let sp = self.the_symbols.get(sp);
+            match &sp.typ {
+                Terminal => return sp.index == la,
+                MultiTerminal(sub_sym) => {
+                    return sub_sym
+                        .iter()
+                        .any(|ss| self.the_symbols.get(ss).index == la)
+                }
+                NonTerminal {
+                    first_set, lambda, ..
+                } => {
+                    if first_set.contains(la) {
+                        return true;
+                    }
+                    if !lambda {
+                        return false;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    fn symbol_names(&self, syms: impl IntoIterator<Item = SymbolId>) -> Vec<String> {
+        syms.into_iter()
+            .map(|sp| self.the_symbols.get(sp).name.clone())
+            .collect()
+    }
+
+    //The state reached from `stp` with the symbol `sp`, if any
+    fn goto_state(&self, stp: StateId, sp: SymbolId) -> Option<StateId> {
+        //A compound token is shifted as any of its tokens
+        let sp = match &self.the_symbols.get(sp).typ {
+            MultiTerminal(sub_sym) => sub_sym[0],
+            _ => sp,
+        };
+        self.the_states.get(stp).actions.iter().find_map(|ap| {
+            let ap = ap.borrow();
+            match ap.detail {
+                ActionDetail::Shift(next) if ap.look_ahead == sp => Some(next),
+                _ => None,
+            }
+        })
+    }
+
+    /* Explain how the parser gets to shift the look-ahead `la` in state `stp`, after reading `path`.
+     ** Return an example sentential form and its derivation.
+     */
+    fn shift_example(
+        &self,
+        path: &[String],
+        stp: StateId,
+        la: usize,
+    ) -> (String, String, Option<(RuleId, usize)>) {
+        for cfp in &self.the_states.get(stp).configs {
+            let cfp = self.the_configs.get(cfp);
+            let rule = self.the_rules.get(cfp.rule);
+            if cfp.dot >= rule.rhs.len() || cfp.dot > path.len() {
+                continue;
+            }
+            if !self.starts_with(&rule.rhs[cfp.dot..cfp.dot + 1], la) {
+                continue;
+            }
+            let (head, before) = path.split_at(path.len() - cfp.dot);
+            let rest = self
+                .symbol_names(rule.rhs[cfp.dot..].iter().map(|s| s.0))
+                .join(" ");
+            let example = join_words(&[&path.join(" "), "*", &rest]);
+            let item = join_words(&[
+                &self.the_symbols.get(rule.lhs.0).name,
+                "::=",
+                &before.join(" "),
+                "*",
+                &rest,
+            ]);
+            let derivation = join_words(&[&head.join(" "), &format!("[{}]", item)]);
+            return (example, derivation, Some((cfp.rule, cfp.dot)));
+        }
+        let la = &self.the_symbols.get(self.symbols[la]).name;
+        let example = join_words(&[&path.join(" "), "*", la, "..."]);
+        (example.clone(), example, None)
+    }
+
+    /* Explain how the parser gets to reduce `rp` in state `stp` with the look-ahead `la`.
+     ** Return the symbols read to get to `stp`, an example sentential form and its derivation.
+     */
+    fn reduce_example(
+        &self,
+        prev: &HashMap<StateId, (StateId, SymbolId)>,
+        froms: &[(Vec<SymbolId>, StateId)],
+        stp: StateId,
+        rp: RuleId,
+        la: usize,
+    ) -> (Vec<String>, String, String) {
+        let rule = self.the_rules.get(rp);
+        let lhs = rule.lhs.0;
+        let rhs = self.symbol_names(rule.rhs.iter().map(|s| s.0)).join(" ");
+        let la_name = &self.the_symbols.get(self.symbols[la]).name;
+        let reduced = format!(
+            "[{}]",
+            join_words(&[&self.the_symbols.get(lhs).name, "::=", &rhs, "*"])
+        );
+
+        //Look for the shortest path to a state that goes to `stp` with the RHS of the rule,
+        //and then with its LHS to a rule that expects the look-ahead.
+        let seq = rule.rhs.iter().map(|s| s.0).collect::<Vec<_>>();
+        //Try first the contexts nearest to the rule, so that the examples are short
+        for depth in 0..8 {
+            if let Some((path, derivation, mut rests)) =
+                self.lookahead_context(froms, stp, &seq, lhs, &reduced, la, depth)
+            {
+                rests.retain(|r| !r.is_empty());
+                let example = join_words(&[&path.join(" "), "*", &rests.join(" ")]);
+                return (path, example, derivation);
+            }
+        }
+
+        //The look-ahead comes from further away, just show that it follows the LHS
+        let path = self.symbol_names(self.access_path(prev, stp));
+        let (head, _) = path.split_at(path.len().saturating_sub(rule.rhs.len()));
+        let example = join_words(&[&path.join(" "), "*", la_name, "..."]);
+        let derivation = join_words(&[&head.join(" "), &reduced, la_name, "..."]);
+        (path, example, derivation)
+    }
+
+    /* Find a context where the look-ahead `la` follows the non-terminal `lhs`, that is reduced
+     ** in `target` after reading `seq`. The derivation of `lhs` is `inner`. Up to `depth` rules
+     ** that end just after `lhs` are climbed to find the look-ahead.
+     ** Return the path to `target`, the derivation and the symbols after the parse point.
+     */
+    #[allow(clippy::too_many_arguments)]
+    fn lookahead_context(
+        &self,
+        froms: &[(Vec<SymbolId>, StateId)],
+        target: StateId,
+        seq: &[SymbolId],
+        lhs: SymbolId,
+        inner: &str,
+        la: usize,
+        depth: usize,
+    ) -> Option<(Vec<String>, String, Vec<String>)> {
+        for (path, from) in froms {
+            let mut to = Some(*from);
+            for sp in seq {
+                to = to.and_then(|to| self.goto_state(to, *sp));
+            }
+            if to != Some(target) {
+                continue;
+            }
+            let goto = match self.goto_state(*from, lhs) {
+                Some(goto) => goto,
+                None => continue,
+            };
+            let path = self.symbol_names(path.iter().copied());
+            let mut path_to = path.clone();
+            path_to.extend(self.symbol_names(seq.iter().copied()));
+            for cfp in &self.the_states.get(goto).basis {
+                let cfp = self.the_configs.get(cfp);
+                let outer = self.the_rules.get(cfp.rule);
+                if cfp.dot == 0 || outer.rhs[cfp.dot - 1].0 != lhs || cfp.dot - 1 > path.len() {
+                    continue;
+                }
+                let before = &outer.rhs[..cfp.dot - 1];
+                let after = &outer.rhs[cfp.dot..];
+                let rest = self.symbol_names(after.iter().map(|s| s.0)).join(" ");
+                let item = format!(
+                    "[{}]",
+                    join_words(&[
+                        &self.the_symbols.get(outer.lhs.0).name,
+                        "::=",
+                        &self.symbol_names(before.iter().map(|s| s.0)).join(" "),
+                        inner,
+                        &rest,
+                    ])
+                );
+                if self.starts_with(after, la) {
+                    let head = &path[..path.len() - before.len()];
+                    let derivation = join_words(&[&head.join(" "), &item]);
+                    return Some((path_to, derivation, vec![rest]));
+                }
+                //If the rest of the rule can be empty, the look-ahead may follow its LHS
+                let nullable = after
+                    .iter()
+                    .all(|SymbolAlias(sp, ..)| self.the_symbols.get(sp).is_lambda());
+                if nullable && depth > 0 {
+                    let before = before.iter().map(|s| s.0).collect::<Vec<_>>();
+                    if let Some((mut path_to, derivation, mut rests)) = self.lookahead_context(
+                        froms,
+                        *from,
+                        &before,
+                        outer.lhs.0,
+                        &item,
+                        la,
+                        depth - 1,
+                    ) {
+                        path_to.extend(self.symbol_names(seq.iter().copied()));
+                        rests.insert(0, rest);
+                        return Some((path_to, derivation, rests));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn rule_text(&self, rp: RuleId) -> String {
+        let rule = self.the_rules.get(rp);
+        join_words(&[
+            &self.the_symbols.get(rule.lhs.0).name,
+            "::=",
+            &self.symbol_names(rule.rhs.iter().map(|s| s.0)).join(" "),
+        ])
+    }
+
+    //The span of any use of the token `la` in the rules
+    fn token_span(&self, la: usize) -> Option<Span> {
+        self.rules.iter().find_map(|rp| {
+            let rule = self.the_rules.get(rp);
+            let found = rule.rhs.iter().find(|SymbolAlias(sp, ..)| {
+                let sp = self.the_symbols.get(sp);
+                match &sp.typ {
+                    Terminal => sp.index == la,
+                    MultiTerminal(sub_sym) => sub_sym
+                        .iter()
+                        .any(|ss| self.the_symbols.get(ss).index == la),
+                    NonTerminal { .. } => false,
+                }
+            });
+            found.map(|SymbolAlias(_, span, _)| *span)
+        })
+    }
+
+    /* Explain every parsing conflict with an example of each of the actions in conflict.
+     ** Each conflict is an error at the rules in conflict and at the look-ahead token.
+     */
+    fn report_conflicts(&self) -> Vec<syn::Error> {
+        let prev = self.access_paths();
+        //Every state with its path, the shortest first
+        let mut froms = self
+            .states
+            .iter()
+            .map(|&from| (self.access_path(&prev, from), from))
+            .collect::<Vec<_>>();
+        froms.sort_by_key(|(path, _)| path.len());
+        let mut errors = Vec::new();
+        for &stp in &self.states {
+            let state = self.the_states.get(stp);
+            let mut primary = None;
+            for ap in &state.actions {
+                let ap = ap.borrow();
+                let sp = self.the_symbols.get(ap.look_ahead);
+                let rp = match &ap.detail {
+                    ActionDetail::Shift(_) | ActionDetail::Reduce(_) => {
+                        primary = Some((ap.look_ahead, ap.detail.clone()));
+                        continue;
+                    }
+                    ActionDetail::SRConflict(rp) | ActionDetail::RRConflict(rp) => *rp,
+                    _ => continue,
+                };
+                let first = match &primary {
+                    Some((la, detail)) if *la == ap.look_ahead => detail,
+                    _ => continue,
+                };
+                //The example of the reduction gives the path for the shift, so both are alike
+                let (path, example, derivation) =
+                    self.reduce_example(&prev, &froms, stp, rp, sp.index);
+                let (kind, first_example, first_rule, la_span) = match first {
+                    ActionDetail::Shift(_) => {
+                        let (example, derivation, item) = self.shift_example(&path, stp, sp.index);
+                        let la_span = item.map(|(rp, dot)| self.the_rules.get(rp).rhs[dot].1);
+                        (
+                            "Shift/reduce",
+                            ("Shift", (example, derivation)),
+                            item.map(|(rp, _)| rp),
+                            la_span,
+                        )
+                    }
+                    ActionDetail::Reduce(rp0) => {
+                        let (_, example, derivation) =
+                            self.reduce_example(&prev, &froms, stp, *rp0, sp.index);
+                        (
+                            "Reduce/reduce",
+                            ("Reduce", (example, derivation)),
+                            Some(*rp0),
+                            None,
+                        )
+                    }
+                    _ => unreachable!("conflict without a primary action"),
+                };
+                let la_span = la_span.or_else(|| self.token_span(sp.index));
+
+                let mut msg = format!(
+                    "{} conflict in state {} on {}",
+                    kind, state.state_num, sp.name
+                );
+                let (first_action, _) = &first_example;
+                match first_rule {
+                    Some(rp0) => write!(
+                        msg,
+                        ", {} with `{}` or reduce with `{}`",
+                        first_action.to_lowercase(),
+                        self.rule_text(rp0),
+                        self.rule_text(rp)
+                    )
+                    .unwrap(),
+                    None => write!(msg, ", shift or reduce with `{}`", self.rule_text(rp)).unwrap(),
+                }
+
+                let reduce_rule = self.the_rules.get(rp);
+                let first = first_rule.map(|rp0| self.the_rules.get(rp0));
+                let (span, location) = match &first {
+                    Some(first) => (first.span, &first.location),
+                    None => (reduce_rule.span, &reduce_rule.location),
+                };
+                let mut error = add_location(location, syn::Error::new(span, msg));
+                //The example and the derivations are errors of their own, so that each one is a
+                //single line
+                for (action, (example, derivation)) in
+                    &[first_example, ("Reduce", (example, derivation))]
+                {
+                    error.combine(add_location(
+                        location,
+                        syn::Error::new(span, format!("{} example: {}", action, example)),
+                    ));
+                    error.combine(add_location(
+                        location,
+                        syn::Error::new(span, format!("{} derivation: {}", action, derivation)),
+                    ));
+                }
+                if first.is_some() && first_rule != Some(rp) {
+                    error.combine(add_location(
+                        &reduce_rule.location,
+                        syn::Error::new(
+                            reduce_rule.span,
+                            format!(
+                                "Conflicting reduction of this rule on {} in state {}",
+                                sp.name, state.state_num
+                            ),
+                        ),
+                    ));
+                }
+                if let Some(la_span) = la_span {
+                    error.combine(syn::Error::new(
+                        la_span,
+                        format!(
+                            "{} is the look-ahead of the conflict in state {}",
+                            sp.name, state.state_num
+                        ),
+                    ));
+                }
+                errors.push(error);
+            }
+        }
+        errors
+    }
+
+    //The maximum number of entries in the parser stack: the start state, plus the longest path of
+    //shifts from any start state. A path is a sequence of states that can be in the stack, so if
+    //there is a cycle the stack is unbounded, and it returns None.
+    fn find_max_stack_depth(&self) -> Option<usize> {
+        fn longest_path(
+            next: &[Vec<usize>],
+            st: usize,
+            longest: &mut [Option<usize>],
+            in_path: &mut [bool],
+        ) -> Option<usize> {
+            if let Some(n) = longest[st] {
+                return Some(n);
+            }
+            if in_path[st] {
+                return None;
+            }
+            in_path[st] = true;
+            let mut n = 0;
+            for &nst in &next[st] {
+                n = n.max(longest_path(next, nst, longest, in_path)? + 1);
+            }
+            in_path[st] = false;
+            longest[st] = Some(n);
+            Some(n)
+        }
+
+        let next = self
+            .states
+            .iter()
+            .map(|stp| {
+                self.the_states
+                    .get(stp)
+                    .actions
+                    .iter()
+                    .filter_map(|ap| match ap.borrow().detail {
+                        ActionDetail::Shift(st) | ActionDetail::SSConflict(st) => {
+                            Some(self.the_states.get(st).state_num)
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let mut longest = vec![None; next.len()];
+        let mut in_path = vec![false; next.len()];
+        let mut depth = 0;
+        for stp in self
+            .states
+            .first()
+            .into_iter()
+            .chain(&self.extra_start_states)
+        {
+            let st = self.the_states.get(stp).state_num;
+            depth = depth.max(longest_path(&next, st, &mut longest, &mut in_path)?);
+        }
+        Some(depth + 1)
+    }
+
+    //The rules that can nest without limit: those with a non-terminal that derives their own LHS,
+    //after the first symbol of the RHS. That is right recursion, such as `list ::= Item list`,
+    //or self-embedding, such as `expr ::= LParen expr RParen`. Left recursion is fine, because
+    //every nested rule is reduced before the next one is shifted.
+    fn unbounded_rules(&self) -> Vec<RuleId> {
+        //The non-terminals used in the rules of every non-terminal, directly or not
+        let mut uses: HashMap<SymbolId, HashSet<SymbolId>> = HashMap::new();
+        for rp in &self.rules {
+            let rule = self.the_rules.get(rp);
+            let used = uses.entry(rule.lhs.0).or_default();
+            for SymbolAlias(sp, ..) in &rule.rhs {
+                if let NonTerminal { .. } = self.the_symbols.get(sp).typ {
+                    used.insert(*sp);
+                }
+            }
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for sp in uses.keys().copied().collect::<Vec<_>>() {
+                let indirect = uses[&sp]
+                    .iter()
+                    .filter_map(|u| uses.get(u))
+                    .flatten()
+                    .copied()
+                    .collect::<Vec<_>>();
+                let used = uses.get_mut(&sp).unwrap();
+                for u in indirect {
+                    changed |= used.insert(u);
+                }
+            }
+        }
+
+        self.rules
+            .iter()
+            .copied()
+            .filter(|rp| {
+                let rule = self.the_rules.get(rp);
+                rule.rhs.iter().skip(1).any(|SymbolAlias(sp, ..)| {
+                    *sp == rule.lhs.0 || matches!(uses.get(sp), Some(u) if u.contains(&rule.lhs.0))
+                })
+            })
+            .collect()
+    }
+
+    fn report_stack_depth(&self) -> String {
+        match self.max_stack_depth {
+            Some(depth) => format!("Maximum stack depth: {}", depth),
+            None => {
+                let mut report = String::from("Unbounded stack depth, because of these rules:");
+                for rp in self.unbounded_rules() {
+                    write!(report, "\n    {}", self.rule_text(rp)).unwrap();
+                }
+                report
+            }
+        }
+    }
+
     fn report_output(&self) -> String {
         let mut state_info = String::new();
         for stp in &self.states {
@@ -1495,7 +3216,7 @@ impl Pomelo {
                    print!(" <lambda>");
                    }
                    for j in 0 .. self.num_terminals {
-                   if first_set.contains(&j) {
+                   if first_set.contains(j) {
                    print!("={}", self.symbols[j].borrow().name);
                    }
                    }
@@ -1507,22 +3228,28 @@ impl Pomelo {
         state_info
     }
 
+    //The key of a state in `state_index`: the (rule, dot) of every config in its sorted basis
+    fn basis_key(&self, bp: &[ConfigId]) -> Vec<(usize, usize)> {
+        bp.iter()
+            .map(|cfp| {
+                let cfp = self.the_configs.get(cfp);
+                (self.the_rules.get(cfp.rule).index, cfp.dot)
+            })
+            .collect()
+    }
+
     fn state_find(&mut self, bp: &[ConfigId]) -> Option<StateId> {
-        let res = self.states.iter().find(|s| {
-            let sbp = &self.the_states.get(*s).basis;
-            if sbp.len() != bp.len() {
-                return false;
-            }
-            for (a, b) in sbp.iter().zip(bp) {
-                let a = self.the_configs.get(a);
-                let b = self.the_configs.get(b);
-                if a.rule != b.rule || a.dot != b.dot {
-                    return false;
-                }
-            }
-            true
-        });
-        res.cloned()
+        let candidates = self.state_index.get(&self.basis_key(bp))?;
+        //With LR(1) states with different lookaheads are different, they may be merged later
+        if !self.is_lr1() {
+            return candidates.first().copied();
+        }
+        candidates.iter().copied().find(|s| {
+            let sbp = &self.the_states.get(s).basis;
+            sbp.iter()
+                .zip(bp)
+                .all(|(a, b)| self.the_configs.get(a).fws == self.the_configs.get(b).fws)
+        })
     }
 
     /* Compute the closure of the configuration list */
@@ -1531,11 +3258,10 @@ impl Pomelo {
         while i < cur.len() {
             //println!("I = {} < {}", i, cur.len());
             let cfp = cur[i];
-            let rhs = self
-                .the_rules
-                .get(self.the_configs.get(cfp).rule)
-                .rhs
-                .clone();
+            let (rhs, location) = {
+                let rule = self.the_rules.get(self.the_configs.get(cfp).rule);
+                (rule.rhs.clone(), rule.location.clone())
+            };
             let dot = self.the_configs.get(cfp).dot;
             if dot < rhs.len() {
                 let SymbolAlias(sp, span, ..) = &rhs[dot];
@@ -1546,7 +3272,8 @@ impl Pomelo {
                     .map(|s| s.to_owned());
                 if let Some(rules) = rules {
                     if rules.is_empty() && *sp != self.error_symbol {
-                        return error_span(*span, "Nonterminal has no rules"); //tested
+                        return error_at(&location, *span, "Nonterminal has no rules");
+                        //tested
                     }
                     for newrp in rules {
                         let newcfp = self.add_config(&mut cur, newrp.clone(), 0);
@@ -1572,7 +3299,7 @@ impl Pomelo {
                                     first_set, lambda, ..
                                 } => {
                                     let mut newcfp = self.the_configs.get_mut(newcfp);
-                                    newcfp.fws.extend(first_set);
+                                    newcfp.fws.union_with(first_set);
                                     if !lambda {
                                         broken = true;
                                         break;
@@ -1624,15 +3351,257 @@ impl Pomelo {
         let sym = self.symbol_new_t(name, typ);
         SymbolSpan(sym, name.span())
     }
-    fn symbol_new(&mut self, name: &str, typ: NewSymbolType) -> SymbolId {
-        if !name.is_empty() {
-            for s in &self.symbols {
-                let mut b = self.the_symbols.get_mut(s);
-                if b.name == name {
-                    b.use_cnt += 1;
-                    //If asked for a MultiTerminal, but it is a Terminal, convert it
-                    if let NewSymbolType::MultiTerminal = typ {
-                        match b.typ {
+    //Gets the hidden non-terminal that stands for the given EBNF construct, creating it if
+    //needed. Its rules are added later, in `prepare()`.
+    fn symbol_new_hidden(&mut self, hidden: Hidden, span: Span) -> SymbolSpan {
+        if let Some(sym_l) = self.hidden_symbols.get(&hidden) {
+            return sym_l.clone();
+        }
+
+        //The name is only used in the reports, but it must be unique
+        let mut name;
+        match &hidden {
+            Hidden::Ebnf(sym_r, quantifier, separator) => {
+                //Separated lists other than T+ % S are built from that one
+                if let Some((sep, trailing)) = *separator {
+                    if trailing || *quantifier != Quantifier::OneOrMore {
+                        self.symbol_new_hidden(
+                            Hidden::Ebnf(*sym_r, Quantifier::OneOrMore, Some((sep, false))),
+                            span,
+                        );
+                    }
+                }
+                name = self.symbol_display_name(*sym_r);
+                name.push(match quantifier {
+                    Quantifier::Optional => '?',
+                    Quantifier::ZeroOrMore => '*',
+                    Quantifier::OneOrMore => '+',
+                    Quantifier::One => unreachable!("plain symbols are not hidden"),
+                });
+                if let Some((sep, trailing)) = separator {
+                    name.push('%');
+                    name.push_str(&self.symbol_display_name(*sep));
+                    if *trailing {
+                        name.push('?');
+                    }
+                }
+            }
+            Hidden::Group(alts) => {
+                let alts = alts
+                    .iter()
+                    .map(|alt| {
+                        alt.iter()
+                            .map(|s| self.symbol_display_name(*s))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    })
+                    .collect::<Vec<_>>();
+                name = format!("({})", alts.join(" | "));
+            }
+            Hidden::Instance(template, args) => {
+                let args = args
+                    .iter()
+                    .map(|s| self.symbol_display_name(*s))
+                    .collect::<Vec<_>>();
+                name = format!("{}({})", template, args.join(", "));
+            }
+        }
+        if self.symbol_find(&name).is_some() {
+            name = format!("{}#{}", name, self.hidden_symbols.len());
+        }
+
+        let sym_l = self.symbol_new_s(&name, NewSymbolType::NonTerminal);
+        let sym_l = SymbolSpan(sym_l, span);
+        self.hidden_symbols.insert(hidden, sym_l.clone());
+        sym_l
+    }
+    //A symbol in the RHS of a rule, with its modifiers
+    fn rhs_symbol(&mut self, rs: RhsSymbol, in_group: bool) -> syn::Result<SymbolAlias> {
+        if in_group {
+            if let Some(alias) = &rs.alias {
+                return error_span(alias.span(), "Aliases are not allowed inside a group");
+                //tested
+            }
+        }
+        let SymbolSpan(tok, span) = match rs.term {
+            RhsTerm::Symbol(toks) => self.symbol_new_rhs(toks)?,
+            RhsTerm::Group(span, alts) => self.symbol_new_group(span, alts)?,
+            RhsTerm::Apply(name, args) => self.symbol_new_instance(name, args)?,
+            RhsTerm::Action(code, ty) => self.symbol_new_action(code, ty.map(|ty| *ty)),
+        };
+        let separator = match rs.separator {
+            Some((seps, trailing)) => {
+                let sep_span = seps[0].span();
+                for sep in &seps {
+                    if !self.bindings.contains_key(&sep.to_string()) && !self.is_terminal(sep)? {
+                        return error_span(sep.span(), "The separator must be a token");
+                        //tested
+                    }
+                }
+                let sep = self.symbol_new_rhs(seps)?.0;
+                //A parameter could be bound to a non-terminal
+                if let NonTerminal { .. } = self.the_symbols.get(sep).typ {
+                    return error_span(sep_span, "The separator must be a token");
+                }
+                Some((sep, trailing))
+            }
+            None => None,
+        };
+        let SymbolSpan(tok, span) = match rs.quantifier {
+            Quantifier::One => SymbolSpan(tok, span),
+            q => self.symbol_new_hidden(Hidden::Ebnf(tok, q, separator), span),
+        };
+        Ok(SymbolAlias(tok, span, rs.alias))
+    }
+    //A mid-rule action `{ code }` is a new hidden non-terminal, with an empty rule that runs the
+    //code, added later in `prepare()`. Every action is different, even if the code is the same.
+    fn symbol_new_action(&mut self, code: Block, ty: Option<Type>) -> SymbolSpan {
+        let span = code.span();
+        let name = format!("@{}", self.mid_actions.len() + 1);
+        let sym = self.symbol_new_s(&name, NewSymbolType::NonTerminal);
+        self.the_symbols.get_mut(sym).data_type = ty.clone();
+        let sym = SymbolSpan(sym, span);
+        self.mid_actions.push((sym.clone(), ty, code));
+        sym
+    }
+    //A parameterized non-terminal `list(A, B)`. The arguments are resolved now, but its rules are
+    //added later, in `expand_templates()`, when all the templates are known.
+    fn symbol_new_instance(
+        &mut self,
+        name: Ident,
+        args: Vec<RhsSymbol>,
+    ) -> syn::Result<SymbolSpan> {
+        let mut sym_args = Vec::new();
+        for rs in args {
+            if let Some(alias) = &rs.alias {
+                return error_span(alias.span(), "Aliases are not allowed in arguments");
+            }
+            sym_args.push(self.rhs_symbol(rs, false)?.0);
+        }
+        let hidden = Hidden::Instance(name.to_string(), sym_args.clone());
+        if let Some(sym) = self.hidden_symbols.get(&hidden) {
+            return Ok(SymbolSpan(sym.0, name.span()));
+        }
+        let sym = self.symbol_new_hidden(hidden, name.span());
+        self.instances.push((
+            name,
+            sym.clone(),
+            sym_args,
+            self.expansion_depth + 1,
+            self.location.clone(),
+        ));
+        Ok(sym)
+    }
+    //A group of alternatives `(a B | C)`. If every alternative is a single token of the same type
+    //it is just a compound `(A | B)` == `A|B`, else it is a new hidden non-terminal.
+    fn symbol_new_group(
+        &mut self,
+        span: Span,
+        alts: Vec<Vec<RhsSymbol>>,
+    ) -> syn::Result<SymbolSpan> {
+        if alts.len() > 26 {
+            return error_span(span, "Too many alternatives in a group");
+        }
+        let mut sym_alts = Vec::new();
+        for alt in alts {
+            let mut syms = Vec::new();
+            for rs in alt {
+                syms.push(self.rhs_symbol(rs, true)?.0);
+            }
+            sym_alts.push(syms);
+        }
+
+        let mut ss = Vec::new();
+        let all_tokens = sym_alts.iter().all(|alt| match &alt[..] {
+            [s] => match &self.the_symbols.get(*s).typ {
+                Terminal => {
+                    ss.push(*s);
+                    true
+                }
+                MultiTerminal(sub_sym) => {
+                    ss.extend(sub_sym.iter().cloned());
+                    true
+                }
+                _ => false,
+            },
+            _ => false,
+        });
+        let same_type = ss
+            .iter()
+            .all(|s| self.the_symbols.get(s).data_type == self.the_symbols.get(ss[0]).data_type);
+        if all_tokens && same_type {
+            let mt = self.symbol_new_s("", NewSymbolType::MultiTerminal);
+            if let MultiTerminal(sub_sym) = &mut self.the_symbols.get_mut(mt).typ {
+                sub_sym.extend(ss);
+            } else {
+                unreachable!();
+            }
+            return Ok(SymbolSpan(mt, span));
+        }
+        Ok(self.symbol_new_hidden(Hidden::Group(sym_alts), span))
+    }
+    fn symbol_display_name(&self, sp: SymbolId) -> String {
+        let sp = self.the_symbols.get(sp);
+        match &sp.typ {
+            MultiTerminal(ss) => ss
+                .iter()
+                .map(|s| self.the_symbols.get(s).name.clone())
+                .collect::<Vec<_>>()
+                .join("|"),
+            _ => sp.name.clone(),
+        }
+    }
+    //A symbol used in the RHS of a rule: a single symbol or a compound `A|B|C`
+    fn symbol_new_rhs(&mut self, toks: Vec<Ident>) -> syn::Result<SymbolSpan> {
+        if toks.len() == 1 {
+            let tok = toks.into_iter().next().unwrap();
+            if let Some(sym) = self.bindings.get(&tok.to_string()) {
+                return Ok(SymbolSpan(*sym, tok.span()));
+            }
+            let nst = if self.is_terminal(&tok)? {
+                NewSymbolType::Terminal
+            } else if self.is_nonterminal(&tok)? {
+                NewSymbolType::NonTerminal
+            } else {
+                return error_span(
+                    tok.span(),
+                    "Symbol must start with uppercase or lowercase letter",
+                ); //tested
+            };
+            Ok(self.symbol_new_t_span(&tok, nst))
+        } else {
+            let mt = self.symbol_new_s("", NewSymbolType::MultiTerminal);
+            let mut ss = Vec::new();
+            let span = toks[0].span(); //TODO: extend span
+            for tok in toks {
+                if self.bindings.contains_key(&tok.to_string()) {
+                    return error_span(tok.span(), "Cannot form a compound containing a parameter");
+                }
+                if !self.is_terminal(&tok)? {
+                    return error_span(
+                        tok.span(),
+                        "Cannot form a compound containing a non-terminal",
+                    ); //tested
+                }
+                ss.push(self.symbol_new_t(&tok, NewSymbolType::Terminal));
+            }
+            if let MultiTerminal(sub_sym) = &mut self.the_symbols.get_mut(mt).typ {
+                sub_sym.extend(ss);
+            } else {
+                unreachable!();
+            }
+            Ok(SymbolSpan(mt, span))
+        }
+    }
+    fn symbol_new(&mut self, name: &str, typ: NewSymbolType) -> SymbolId {
+        if !name.is_empty() {
+            for s in &self.symbols {
+                let mut b = self.the_symbols.get_mut(s);
+                if b.name == name {
+                    b.use_cnt += 1;
+                    //If asked for a MultiTerminal, but it is a Terminal, convert it
+                    if let NewSymbolType::MultiTerminal = typ {
+                        match b.typ {
                             MultiTerminal { .. } => (),
                             Terminal => {
                                 b.typ = MultiTerminal(Vec::new());
@@ -1663,6 +3632,8 @@ impl Pomelo {
             data_type: None,
             dt_num: 0,
             attributes: Vec::new(),
+            decl_span: None,
+            decl_location: None,
         };
 
         let id = self.the_symbols.push(symbol);
@@ -1678,6 +3649,28 @@ impl Pomelo {
         }
         None
     }
+    //Some(true) for a token and Some(false) for a non-terminal, if declared with %terminal or
+    //%nonterminal. Else its kind is given by the case of its first letter, unless in %strict mode.
+    fn declared_kind(&self, id: &Ident) -> syn::Result<Option<bool>> {
+        match self.declared.get(&id.to_string()) {
+            Some(terminal) => Ok(Some(*terminal)),
+            None if self.strict && id != "error" => error_span(
+                id.span(),
+                "Undeclared symbol, use %terminal or %nonterminal",
+            ), //tested
+            None => Ok(None),
+        }
+    }
+    fn is_terminal(&self, id: &Ident) -> syn::Result<bool> {
+        Ok(self
+            .declared_kind(id)?
+            .unwrap_or_else(|| is_terminal_ident(id)))
+    }
+    fn is_nonterminal(&self, id: &Ident) -> syn::Result<bool> {
+        Ok(self
+            .declared_kind(id)?
+            .map_or_else(|| is_nonterminal_ident(id), |terminal| !terminal))
+    }
 
     /*
      ** Compare two states for sorting purposes.  The smaller state is the
@@ -1718,10 +3711,51 @@ impl Pomelo {
                 }
                 self.stack_overflow = Some(code);
             }
-            Decl::Type(attrs, id, ty) => {
-                let nst = if is_terminal_ident(&id) {
+            Decl::Expect(n) => {
+                if self.expect.is_some() {
+                    return error_span(n.span(), "Expected conflicts already defined");
+                    //tested
+                }
+                self.expect = Some(n.base10_parse()?);
+            }
+            Decl::ExpectRR(n) => {
+                if self.expect_rr.is_some() {
+                    return error_span(n.span(), "Expected conflicts already defined");
+                }
+                self.expect_rr = Some(n.base10_parse()?);
+            }
+            Decl::Destructor(id, alias, code) => {
+                if self.destructors.iter().any(|(d, _, _)| *d == id) {
+                    return error_span(id.span(), "Destructor already defined"); //tested
+                }
+                self.destructors.push((id, alias, code));
+            }
+            Decl::TokenDestructor(code) => {
+                if self.token_destructor.is_some() {
+                    return error_span(code.span(), "Token destructor already defined");
+                }
+                self.token_destructor = Some(code);
+            }
+            Decl::Type(attrs, id, params, ty) if !params.is_empty() => {
+                if !is_nonterminal_ident(&id) {
+                    return error_span(id.span(), "Only non-terminals can have parameters");
+                }
+                if !attrs.is_empty() {
+                    return error_span(
+                        attrs[0].span(),
+                        "Non terminal symbol cannot have attributes",
+                    );
+                }
+                let template = self.templates.entry(id.to_string()).or_default();
+                if template.data_type.is_some() {
+                    return error_span(id.span(), "Symbol type already defined");
+                }
+                template.data_type = ty.map(|ty| (params, ty));
+            }
+            Decl::Type(attrs, id, _, ty) => {
+                let nst = if self.is_terminal(&id)? {
                     NewSymbolType::Terminal
-                } else if is_nonterminal_ident(&id) {
+                } else if self.is_nonterminal(&id)? {
                     if !attrs.is_empty() {
                         return error_span(
                             attrs[0].span(),
@@ -1743,11 +3777,15 @@ impl Pomelo {
                 }
                 sp.data_type = ty;
                 sp.attributes = attrs;
+                if sp.decl_span.is_none() {
+                    sp.decl_span = Some(id.span());
+                    sp.decl_location = self.location.clone();
+                }
             }
             Decl::Assoc(a, ids) => {
                 pdt.precedence += 1;
                 for token in ids {
-                    if !is_terminal_ident(&token) {
+                    if !self.is_terminal(&token)? {
                         return error_span(
                             token.span(),
                             "Precedence cannot be assigned to a non-terminal",
@@ -1764,6 +3802,10 @@ impl Pomelo {
                         } //tested
                         None => b.precedence = Some(Precedence(pdt.precedence, a)),
                     }
+                    if b.decl_span.is_none() {
+                        b.decl_span = Some(token.span());
+                        b.decl_location = self.location.clone();
+                    }
                 }
             }
             Decl::DefaultType(ty) => {
@@ -1786,25 +3828,40 @@ impl Pomelo {
                 }
                 self.err_type = Some(ty);
             }
-            Decl::StartSymbol(id) => {
+            Decl::StartSymbol(id, others) => {
                 if self.start.is_some() {
                     return error_span(id.span(), "Start symbol already defined");
                     //tested
                 }
-                if !is_nonterminal_ident(&id) {
-                    return error_span(id.span(), "Start symbol must be a non-terminal");
-                    //tested
+                for id in std::iter::once(&id).chain(&others) {
+                    if !self.is_nonterminal(id)? {
+                        return error_span(id.span(), "Start symbol must be a non-terminal");
+                        //tested
+                    }
                 }
                 self.start = Some(self.symbol_new_t(&id, NewSymbolType::NonTerminal));
+
+                //Every additional start symbol `S` gets an augmented non-terminal `S'`, with the
+                //rule `S' ::= S`, so that `S` can be used freely in the RHS of other rules
+                for (i, other) in others.iter().enumerate() {
+                    if *other == id || others[..i].contains(other) {
+                        return error_span(other.span(), "Duplicated start symbol");
+                        //tested
+                    }
+                    self.symbol_new_t(other, NewSymbolType::NonTerminal);
+                    let augmented =
+                        self.symbol_new_s(&format!("{}'", other), NewSymbolType::NonTerminal);
+                    self.extra_starts.push((other.clone(), augmented));
+                }
             }
             Decl::Fallback(fb, ids) => {
-                if !is_terminal_ident(&fb) {
+                if !self.is_terminal(&fb)? {
                     return error_span(fb.span(), "Fallback must be a token"); //tested
                 }
                 let fallback = self.symbol_new_t(&fb, NewSymbolType::Terminal);
                 let mut has_fallback = self.has_fallback;
                 for id in ids {
-                    if !is_terminal_ident(&id) {
+                    if !self.is_terminal(&id)? {
                         return error_span(id.span(), "Fallback ids must be tokens");
                         //tested
                     }
@@ -1819,24 +3876,35 @@ impl Pomelo {
                 }
                 self.has_fallback = has_fallback;
             }
+            Decl::Inline(ids) => {
+                for id in ids {
+                    if !self.is_nonterminal(&id)? {
+                        return error_span(id.span(), "Inline symbol must be a non-terminal");
+                    }
+                    let sp = self.symbol_new_t_span(&id, NewSymbolType::NonTerminal);
+                    if !self.inline.iter().any(|s| s.0 == sp.0) {
+                        self.inline.push(sp);
+                    }
+                }
+            }
             Decl::Wildcard(id) => {
                 if self.wildcard.is_some() {
                     return error_span(id.span(), "Wildcard already defined"); //tested
                 }
-                if !is_terminal_ident(&id) {
+                if !self.is_terminal(&id)? {
                     return error_span(id.span(), "Wildcard must be a token"); //tested
                 }
                 let sp = self.symbol_new_t(&id, NewSymbolType::Terminal);
                 self.wildcard = Some(sp);
             }
             Decl::TokenClass(tk, ids) => {
-                if !is_terminal_ident(&tk) {
+                if !self.is_terminal(&tk)? {
                     return error_span(tk.span(), "token_class must be a token");
                     //tested
                 }
                 let tk = self.symbol_new_t(&tk, NewSymbolType::MultiTerminal);
                 for id in ids {
-                    if !is_terminal_ident(&id) {
+                    if !self.is_terminal(&id)? {
                         return error_span(id.span(), "token_class ids must be tokens");
                         //tested
                     }
@@ -1871,89 +3939,126 @@ impl Pomelo {
                 if self.stack_type.is_some() {
                     return error_span(ty.span(), "Stack size already defined");
                 }
-                self.stack_limit = limit;
+                match limit {
+                    Some(limit) => self.stack_limit = limit,
+                    None => self.stack_auto = true,
+                }
                 self.stack_type = ty;
             }
             Decl::Verbose => {
                 self.verbose = true;
             }
+            Decl::Strict => {
+                self.strict = true;
+            }
+            Decl::Terminal(ids) => {
+                for id in ids {
+                    self.declare_symbol(id, true)?;
+                }
+            }
+            Decl::NonTerminal(ids) => {
+                for id in ids {
+                    self.declare_symbol(id, false)?;
+                }
+            }
+            Decl::Glr => {
+                self.glr = true;
+            }
+            Decl::Merge(id, a, b, code) => {
+                if self.merges.iter().any(|(m, ..)| *m == id) {
+                    return error_span(id.span(), "Merge already defined");
+                }
+                self.merges.push((id, a, b, code));
+            }
+            Decl::Deny(ids) => {
+                for id in ids {
+                    let lint = Lint::from_ident(&id)?;
+                    if !self.lints.contains(&lint) {
+                        self.lints.push(lint);
+                    }
+                }
+            }
+            Decl::Allow(ids) => {
+                for id in ids {
+                    let lint = Lint::from_ident(&id)?;
+                    self.lints.retain(|l| *l != lint);
+                }
+            }
+            Decl::Algorithm(id) => {
+                if self.algorithm.is_some() {
+                    return error_span(id.span(), "Algorithm already defined");
+                }
+                self.algorithm = Some(match id.to_string().as_str() {
+                    "lalr" => Algorithm::Lalr,
+                    "lr1" => Algorithm::Lr1,
+                    "minimal" => Algorithm::Minimal,
+                    _ => {
+                        return error_span(
+                            id.span(),
+                            "Unknown algorithm, expected lalr, lr1 or minimal",
+                        ); //tested
+                    }
+                });
+            }
+            Decl::Tables(id) => {
+                if self.tables.is_some() {
+                    return error_span(id.span(), "Tables already defined");
+                }
+                self.tables = Some(match id.to_string().as_str() {
+                    "compressed" => Tables::Compressed,
+                    "dense" => Tables::Dense,
+                    _ => {
+                        return error_span(
+                            id.span(),
+                            "Unknown tables, expected compressed or dense",
+                        );
+                        //tested
+                    }
+                });
+            }
             Decl::Rule {
                 lhs,
+                params,
                 rhs,
                 action,
                 prec,
             } => {
                 let lhs_span = lhs.span();
-                if !is_nonterminal_ident(&lhs) {
+                //Parameterized non-terminals are not symbols, so they are never declared
+                let is_nonterminal = if params.is_empty() {
+                    self.is_nonterminal(&lhs)?
+                } else {
+                    is_nonterminal_ident(&lhs)
+                };
+                if !is_nonterminal {
                     return error_span(lhs_span, "LHS of rule must be non-terminal");
                     //tested
                 }
+                //Parameterized rules are expanded later, for every use
+                if !params.is_empty() {
+                    let template = self.templates.entry(lhs.to_string()).or_default();
+                    if let Some(first) = template.rules.first() {
+                        if first.params.len() != params.len() {
+                            return error_span(lhs_span, "Wrong number of parameters");
+                            //tested
+                        }
+                    }
+                    template.rules.push(TemplateRule {
+                        span: lhs_span,
+                        params,
+                        rhs,
+                        action,
+                        prec,
+                        location: self.location.clone(),
+                    });
+                    return Ok(());
+                }
                 let lhs = self.symbol_new_t_span(&lhs, NewSymbolType::NonTerminal);
                 let rhs = rhs
                     .into_iter()
-                    .map(|(toks, optional, alias)| {
-                        let SymbolSpan(tok, span) = if toks.len() == 1 {
-                            let tok = toks.into_iter().next().unwrap();
-                            let nst = if is_terminal_ident(&tok) {
-                                NewSymbolType::Terminal
-                            } else if is_nonterminal_ident(&tok) {
-                                NewSymbolType::NonTerminal
-                            } else {
-                                return error_span(
-                                    tok.span(),
-                                    "Symbol must start with uppercase or lowercase letter",
-                                ); //tested
-                            };
-                            if optional {
-                                let sym_r = self.symbol_new_t(&tok, nst);
-                                if let Some(sym_l) = self.optional_tokens.get(&sym_r) {
-                                    sym_l.clone()
-                                } else {
-                                    let sym_l = self.symbol_new_s(
-                                        &format!("_{}", self.optional_tokens.len()),
-                                        NewSymbolType::NonTerminal,
-                                    );
-                                    let sym_l = SymbolSpan(sym_l, tok.span());
-                                    self.optional_tokens.insert(sym_r, sym_l.clone());
-                                    sym_l
-                                }
-                            } else {
-                                self.symbol_new_t_span(&tok, nst)
-                            }
-                        } else {
-                            let mt = self.symbol_new_s("", NewSymbolType::MultiTerminal);
-                            let mut ss = Vec::new();
-                            let span = toks[0].span(); //TODO: extend span
-                            for tok in toks {
-                                if !is_terminal_ident(&tok) {
-                                    return error_span(
-                                        tok.span(),
-                                        "Cannot form a compound containing a non-terminal",
-                                    ); //tested
-                                }
-                                ss.push(self.symbol_new_t(&tok, NewSymbolType::Terminal));
-                            }
-                            if let MultiTerminal(sub_sym) = &mut self.the_symbols.get_mut(mt).typ {
-                                sub_sym.extend(ss);
-                            } else {
-                                unreachable!();
-                            }
-                            SymbolSpan(mt, span)
-                        };
-                        Ok(SymbolAlias(tok, span, alias))
-                    })
+                    .map(|rs| self.rhs_symbol(rs, false))
                     .collect::<syn::Result<Vec<_>>>()?;
-
-                let prec_sym = match &prec {
-                    Some(id) => {
-                        if !is_terminal_ident(id) {
-                            return error_span(id.span(), "The precedence symbol must be a token");
-                            //tested
-                        }
-                        Some(self.symbol_new_t(id, NewSymbolType::Terminal))
-                    }
-                    None => None,
-                };
+                let prec_sym = self.rule_precedence(prec)?;
 
                 self.create_rule(lhs_span, lhs, rhs, action, prec_sym);
             }
@@ -1961,6 +4066,146 @@ impl Pomelo {
         Ok(())
     }
 
+    //A symbol declared with %terminal or %nonterminal, whatever the case of its name
+    fn declare_symbol(&mut self, id: Ident, terminal: bool) -> syn::Result<()> {
+        if id == "error" || self.declared.insert(id.to_string(), terminal).is_some() {
+            return error_span(id.span(), "Symbol already declared"); //tested
+        }
+        let nst = if terminal {
+            NewSymbolType::Terminal
+        } else {
+            NewSymbolType::NonTerminal
+        };
+        let sp = self.symbol_new_t(&id, nst);
+        let mut sp = self.the_symbols.get_mut(sp);
+        if sp.decl_span.is_none() {
+            sp.decl_span = Some(id.span());
+            sp.decl_location = self.location.clone();
+        }
+        Ok(())
+    }
+
+    fn rule_precedence(&mut self, prec: Option<Ident>) -> syn::Result<Option<SymbolId>> {
+        match &prec {
+            Some(id) => {
+                if !self.is_terminal(id)? {
+                    return error_span(id.span(), "The precedence symbol must be a token");
+                    //tested
+                }
+                Ok(Some(self.symbol_new_t(id, NewSymbolType::Terminal)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    //Adds the rules of every instance of a parameterized non-terminal, replacing the parameters
+    //with the arguments. New instances may be found while doing so.
+    fn expand_templates(&mut self) -> syn::Result<()> {
+        let mut next = 0;
+        while next < self.instances.len() {
+            let (name, lhs, args, depth, location) = self.instances[next].clone();
+            next += 1;
+            let template = match self.templates.get(&name.to_string()) {
+                Some(template) if !template.rules.is_empty() => template,
+                _ => {
+                    return error_at(
+                        &location,
+                        name.span(),
+                        "Parameterized non-terminal has no rules",
+                    )
+                }
+            };
+            if template.rules[0].params.len() != args.len() {
+                return error_at(&location, name.span(), "Wrong number of arguments");
+                //tested
+            }
+            if let Some((params, _)) = &template.data_type {
+                if params.len() != args.len() {
+                    return error_span(params[0].span(), "Wrong number of parameters");
+                }
+            }
+            if depth > MAX_EXPANSION_DEPTH {
+                return error_at(
+                    &location,
+                    name.span(),
+                    "Parameterized non-terminal expands infinitely",
+                ); //tested
+            }
+            self.expansion_depth = depth;
+            for rule in template.rules.clone() {
+                self.location = rule.location;
+                self.bindings = rule
+                    .params
+                    .iter()
+                    .map(|p| p.to_string())
+                    .zip(args.iter().cloned())
+                    .collect();
+                let rhs = rule
+                    .rhs
+                    .into_iter()
+                    .map(|rs| self.rhs_symbol(rs, false))
+                    .collect::<syn::Result<Vec<_>>>()
+                    .map_err(|e| add_location(&self.location, e))?;
+                let prec_sym = self
+                    .rule_precedence(rule.prec)
+                    .map_err(|e| add_location(&self.location, e))?;
+                self.create_rule(rule.span, lhs.clone(), rhs, rule.action, prec_sym);
+            }
+        }
+        self.bindings.clear();
+        self.instances.clear();
+
+        for (name, template) in &self.templates {
+            if self.symbol_find(name).is_some() {
+                let (span, location) = match (template.rules.first(), &template.data_type) {
+                    (Some(rule), _) => (rule.span, &rule.location),
+                    (None, Some((params, _))) => (params[0].span(), &None),
+                    (None, None) => (Span::call_site(), &None),
+                };
+                return error_at(
+                    location,
+                    span,
+                    "Non-terminal with parameters cannot be used without them",
+                ); //tested
+            }
+        }
+        Ok(())
+    }
+
+    //Tokens used only to give precedence to rules, such as `UMinus` in `[UMinus]`. They are never
+    //in the input, so they are not added to the Token enum.
+    fn precedence_only_symbols(&self) -> Vec<SymbolId> {
+        let mut used = Vec::new();
+        let mut prec_syms = Vec::new();
+        for rp in &self.rules {
+            let rp = self.the_rules.get(rp);
+            used.extend(rp.rhs.iter().map(|SymbolAlias(sp, ..)| *sp));
+            prec_syms.extend(rp.prec_sym);
+        }
+        for sp in &self.symbols {
+            let s = self.the_symbols.get(sp);
+            if let MultiTerminal(ss) = &s.typ {
+                used.extend(ss);
+            }
+            if let Some(fb) = s.fallback {
+                used.push(fb);
+                used.push(*sp);
+            }
+        }
+        used.extend(self.wildcard);
+
+        self.symbols[1..self.num_terminals]
+            .iter()
+            .filter(|sp| {
+                let s = self.the_symbols.get(*sp);
+                !used.contains(sp)
+                    && s.attributes.is_empty()
+                    && (s.precedence.is_some() || prec_syms.contains(sp))
+            })
+            .copied()
+            .collect()
+    }
+
     fn create_rule(
         &mut self,
         span: Span,
@@ -1980,6 +4225,7 @@ impl Pomelo {
             precedence: None,
             index,
             can_reduce: false,
+            location: self.location.clone(),
         };
         let id = self.the_rules.push(rule);
         let rule = self.the_rules.get_mut(id);
@@ -1999,6 +4245,7 @@ impl Pomelo {
             #![allow(unreachable_code)]
             #![allow(unused_variables)]
             #![allow(non_snake_case)]
+            #![allow(non_camel_case_types)]
             #![allow(unused_braces)]
         });
 
@@ -2006,9 +4253,23 @@ impl Pomelo {
             code.to_tokens(&mut src);
         }
 
+        //The values of groups with alternatives of different types
+        for &n in &self.alt_enums {
+            let alt_enum = Ident::new(&format!("Alt{}", n), Span::call_site());
+            let variants = (0..n)
+                .map(|i| Ident::new(&((b'A' + i as u8) as char).to_string(), Span::call_site()))
+                .collect::<Vec<_>>();
+            src.extend(quote! {
+                #[derive(Debug, Clone, PartialEq)]
+                pub enum #alt_enum<#(#variants),*> {
+                    #(#variants(#variants)),*
+                }
+            });
+        }
+
         /* Generate the defines */
         let yycodetype = minimum_signed_type(self.symbols.len());
-        let yyactiontype = minimum_unsigned_type(self.states.len() + self.rules.len() + 5);
+        let yyactiontype = minimum_unsigned_type(self.states.len() + 2 * self.rules.len() + 5);
         let yynocode = (self.symbols.len()) as i32;
         let yywildcard = if let Some(wildcard) = self.wildcard {
             let wildcard = self.the_symbols.get(wildcard);
@@ -2034,6 +4295,14 @@ impl Pomelo {
             type YYStack<T> = #yystacktype<T>;
             const YYSTACKLIMIT: usize = #yystacklimit;
         });
+        //Public, so that users can choose a size for their own stack type
+        let max_stack_depth = match self.max_stack_depth {
+            Some(depth) => quote!(Some(#depth)),
+            None => quote!(None),
+        };
+        src.extend(quote! {
+            pub const MAX_STACK_DEPTH: Option<usize> = #max_stack_depth;
+        });
 
         /*
          ** Print the definition of the union used for the parser's data stack.
@@ -2045,12 +4314,20 @@ impl Pomelo {
         //Maps the Type to the equivalent dt_num
         let mut types = HashMap::<Type, usize>::new();
 
+        //Mid-rule actions without a type have no value, instead of the default type
+        let untyped_actions = self
+            .mid_actions
+            .iter()
+            .filter(|(_, ty, _)| ty.is_none())
+            .map(|(sym, _, _)| sym.0)
+            .collect::<Vec<_>>();
         for sp in &self.symbols {
             if let Some(wildcard) = &self.wildcard {
                 if sp == wildcard {
                     continue;
                 }
             }
+            let has_default = !untyped_actions.contains(sp);
 
             let mut sp = self.the_symbols.get_mut(sp);
 
@@ -2067,7 +4344,9 @@ impl Pomelo {
                 SymbolType::NonTerminal { .. } => sp.data_type.clone(),
             };
 
-            sp.data_type = data_type.or_else(|| self.default_type.clone());
+            if has_default {
+                sp.data_type = data_type.or_else(|| self.default_type.clone());
+            }
             sp.dt_num = match &sp.data_type {
                 None => 0,
                 Some(cp) => {
@@ -2130,7 +4409,10 @@ impl Pomelo {
             }
         };
 
-        let minor_types = types.iter().map(|(k, v)| {
+        //In the order of dt_num, to get the same code every time
+        let mut minor_types = types.iter().collect::<Vec<_>>();
+        minor_types.sort_by_key(|(_, v)| **v);
+        let minor_types = minor_types.into_iter().map(|(k, v)| {
             let ident = Ident::new(&format!("YY{}", v), Span::call_site());
             quote!(#ident(#k))
         });
@@ -2153,107 +4435,31 @@ impl Pomelo {
             0
         };
         let yyerrorsymbol = Literal::usize_unsuffixed(yyerrorsymbol);
+        let yyntoken = Literal::usize_unsuffixed(self.num_terminals);
 
         src.extend(quote!(
             const YYNSTATE: i32 = #yynstate;
             const YYNRULE: i32 = #yynrule;
+            const YY_MIN_SHIFTREDUCE: i32 = YYNSTATE + YYNRULE + 3;
+            const YYNTOKEN: i32 = #yyntoken;
             const YYERRORSYMBOL: i32 = #yyerrorsymbol;
         ));
 
-        /* Generate the action table and its associates:
-         **
-         **  yy_action[]        A single table containing all actions.
-         **  yy_lookahead[]     A table containing the lookahead for each entry in
-         **                     yy_action.  Used to detect hash collisions.
-         **  yy_shift_ofst[]    For each state, the offset into yy_action for
-         **                     shifting terminals.
-         **  yy_reduce_ofst[]   For each state, the offset into yy_action for
-         **                     shifting non-terminals after a reduce.
-         **  yy_default[]       Default action for each state.
-         */
-
-        let mut ax = Vec::with_capacity(2 * self.states.len());
-        /* Compute the actions on all states and count them up */
-        for stp in &self.states {
-            ax.push(AxSet {
-                stp: *stp,
-                is_tkn: true,
-                n_action: self.the_states.get(stp).n_tkn_act,
-            });
-            ax.push(AxSet {
-                stp: *stp,
-                is_tkn: false,
-                n_action: self.the_states.get(stp).n_nt_act,
-            });
+        match self.tables {
+            Some(Tables::Dense) => self.generate_dense_tables(&mut src, &yyactiontype),
+            _ => self.generate_compressed_tables(&mut src, &yycodetype, &yyactiontype),
         }
 
-        ax.sort_by_key(|a| a.n_action);
-        ax.reverse();
-
-        let mut max_tkn_ofst = 0;
-        let mut min_tkn_ofst = 0;
-        let mut max_nt_ofst = 0;
-        let mut min_nt_ofst = 0;
-
-        /* Compute the action table.  In order to try to keep the size of the
-         ** action table to a minimum, the heuristic of placing the largest action
-         ** sets first is used.
-         */
-        let mut acttab = ActTab::new();
-
-        for a in &ax {
-            let mut actset = ActionSet::new();
-
-            if a.n_action == 0 {
-                continue;
-            }
-            if a.is_tkn {
-                for ap in &self.the_states.get(a.stp).actions {
-                    let ap = ap.borrow();
-                    let sp = self.the_symbols.get(ap.look_ahead);
-                    if sp.index >= self.num_terminals {
-                        continue;
-                    }
-                    match self.compute_action(&ap) {
-                        None => continue,
-                        Some(action) => actset.add_action(sp.index, action),
-                    }
-                }
-                let ofs = acttab.insert_action_set(&actset);
-                let mut stp = self.the_states.get_mut(a.stp);
-                stp.i_tkn_ofst = Some(ofs);
-                min_tkn_ofst = cmp::min(ofs, min_tkn_ofst);
-                max_tkn_ofst = cmp::max(ofs, max_tkn_ofst);
-            } else {
-                for ap in &self.the_states.get(a.stp).actions {
-                    let ap = ap.borrow();
-                    let sp = self.the_symbols.get(ap.look_ahead);
-                    if sp.index < self.num_terminals {
-                        continue;
-                    }
-                    if sp.index == self.default_index {
-                        continue;
-                    }
-                    //sp is a non-default NonTerminal
-                    match self.compute_action(&ap) {
-                        None => continue,
-                        Some(action) => actset.add_action(sp.index, action),
-                    }
-                }
-                let ofs = acttab.insert_action_set(&actset);
-                let mut stp = self.the_states.get_mut(a.stp);
-                stp.i_nt_ofst = Some(ofs);
-                min_nt_ofst = cmp::min(ofs, min_nt_ofst);
-                max_nt_ofst = cmp::max(ofs, max_nt_ofst);
-            }
-        }
-        /* Output the yy_action table */
-        let yytoken_span = yytoken.brace_token.span;
+        let yytoken_span = yytoken.brace_token.span;
 
         let mut token_matches = Vec::new();
         let mut token_builds = Vec::new();
         let mut token_extra = Vec::new();
+        let precedence_only = self.precedence_only_symbols();
         for i in 1..self.num_terminals {
+            if precedence_only.contains(&self.symbols[i]) {
+                continue;
+            }
             let s = self.the_symbols.get(self.symbols[i]);
             let i = i as i32;
             let name = Ident::new(&s.name, Span::call_site());
@@ -2330,85 +4536,6 @@ impl Pomelo {
                 }
             ));
         }
-        let yy_action = acttab
-            .a_action
-            .iter()
-            .map(|ac| match ac {
-                None => (self.states.len() + self.rules.len() + 2) as i32,
-                Some(a) => a.action as i32,
-            })
-            .map(Literal::i32_unsuffixed);
-        let yy_action_len = yy_action.len();
-        src.extend(quote!(static YY_ACTION: [i32; #yy_action_len] = [ #(#yy_action),* ];));
-
-        /* Output the yy_lookahead table */
-        let yy_lookahead = acttab
-            .a_action
-            .iter()
-            .map(|ac| match ac {
-                None => self.default_index,
-                Some(a) => a.lookahead,
-            })
-            .map(Literal::usize_unsuffixed);
-        let yy_lookahead_len = yy_lookahead.len();
-        src.extend(
-            quote!(static YY_LOOKAHEAD: [#yycodetype; #yy_lookahead_len] = [ #(#yy_lookahead),* ];),
-        );
-
-        /* Output the yy_shift_ofst[] table */
-        let n = self
-            .states
-            .iter()
-            .rposition(|st| self.the_states.get(st).i_tkn_ofst.is_some())
-            .unwrap();
-        let yy_shift_use_dflt = min_tkn_ofst - 1;
-        src.extend(quote!(const YY_SHIFT_USE_DFLT: i32 = #yy_shift_use_dflt;));
-        src.extend(quote!(const YY_SHIFT_COUNT: i32 = #n as i32;));
-        src.extend(quote!(const YY_SHIFT_MIN: i32 = #min_tkn_ofst;));
-        src.extend(quote!(const YY_SHIFT_MAX: i32 = #max_tkn_ofst;));
-        let yy_shift_ofst_type = minimum_signed_type(max_tkn_ofst as usize);
-        let yy_shift_ofst = self.states[0..=n]
-            .iter()
-            .map(|stp| {
-                let stp = self.the_states.get(stp);
-                stp.i_tkn_ofst.unwrap_or(min_tkn_ofst - 1)
-            })
-            .map(Literal::i32_unsuffixed);
-        let yy_shift_ofst_len = yy_shift_ofst.len();
-        src.extend(quote!(static YY_SHIFT_OFST: [#yy_shift_ofst_type; #yy_shift_ofst_len] = [ #(#yy_shift_ofst),* ];));
-
-        /* Output the yy_reduce_ofst[] table */
-        let n = self
-            .states
-            .iter()
-            .rposition(|st| self.the_states.get(st).i_nt_ofst.is_some())
-            .unwrap();
-        let yy_reduce_use_dflt = min_nt_ofst - 1;
-        src.extend(quote!(const YY_REDUCE_USE_DFLT: i32 = #yy_reduce_use_dflt;));
-        src.extend(quote!(const YY_REDUCE_COUNT: i32 = #n as i32;));
-        src.extend(quote!(const YY_REDUCE_MIN: i32 = #min_nt_ofst;));
-        src.extend(quote!(const YY_REDUCE_MAX: i32 = #max_nt_ofst;));
-        let yy_reduce_ofst_type = minimum_signed_type(max_nt_ofst as usize);
-        let yy_reduce_ofst = self.states[0..=n]
-            .iter()
-            .map(|stp| {
-                let stp = self.the_states.get(stp);
-                stp.i_nt_ofst.unwrap_or(min_nt_ofst - 1)
-            })
-            .map(Literal::i32_unsuffixed);
-        let yy_reduce_ofst_len = yy_reduce_ofst.len();
-        src.extend(quote!(static YY_REDUCE_OFST: [#yy_reduce_ofst_type; #yy_reduce_ofst_len] = [ #(#yy_reduce_ofst),* ];));
-
-        let yy_default = self
-            .states
-            .iter()
-            .map(|stp| self.the_states.get(stp).i_dflt)
-            .map(Literal::usize_unsuffixed);
-        let yy_default_len = yy_default.len();
-        src.extend(
-            quote!(static YY_DEFAULT: [#yyactiontype; #yy_default_len] = [ #(#yy_default),* ];),
-        );
-
         /* Generate the table of fallback tokens. */
         let mx = self
             .symbols
@@ -2462,11 +4589,30 @@ impl Pomelo {
         let yyroottype = start.data_type.as_ref().unwrap_or(&unit_type);
         let yyerrtype = self.err_type.as_ref().unwrap_or(&unit_type);
 
+        //With %glr the parser keeps the tops of the graph-structured stack, and the node for the top
+        //of the LR stack, where the graph starts. The accepted value is a vector of alternatives.
+        let (yyglrfields, yyglrinit, yyaccepttype) = if self.glr {
+            (
+                quote! {
+                    yyglr: std::vec::Vec<std::rc::Rc<YYGlrNode #yy_generics>>,
+                    yyglrbase: Option<std::rc::Rc<YYGlrNode #yy_generics>>,
+                },
+                quote! {
+                    yyglr: std::vec::Vec::new(),
+                    yyglrbase: None,
+                },
+                quote!(std::vec::Vec<YYMinorType #yy_generics>),
+            )
+        } else {
+            (quote!(), quote!(), quote!(YYMinorType #yy_generics))
+        };
+
         let parser_fields = parse_quote!({
             error_count: u8, /* Shift since last error */
             yystack: YYStack<YYStackEntry #yy_generics>,
             extra: #yyextratype,
-            yystatus: YYStatus<#yyroottype>,
+            yystatus: YYStatus<#yyaccepttype>,
+            #yyglrfields
         });
         yyparser.fields = syn::Fields::Named(parser_fields);
 
@@ -2503,22 +4649,48 @@ impl Pomelo {
             #yyparser
         });
 
+        //The accepted value is stored as a YYMinorType, get it back with the type of the start symbol.
+        //With %glr there is a vector of them, one for each ambiguity that is not merged.
+        let root_value = |ty: &Type| {
+            let value = match types.get(ty) {
+                Some(n) => {
+                    let yyroot = Ident::new(&format!("YY{}", n), Span::call_site());
+                    quote!(match root {
+                        YYMinorType::#yyroot(root) => root,
+                        _ => unreachable!("unexpected root type"),
+                    })
+                }
+                None => quote!(()),
+            };
+            if self.glr {
+                (
+                    quote!(std::vec::Vec<#ty>),
+                    quote!(root.into_iter().map(|root| #value).collect()),
+                )
+            } else {
+                (quote!(#ty), value)
+            }
+        };
+        let (yyroottype, yyrootvalue) = root_value(yyroottype);
+
         let impl_parser = if *yyextratype == unit_type {
             quote! {
                 pub fn new() -> Self {
-                    Self::new_priv(())
+                    Self::new_priv((), 0)
                 }
                 pub fn end_of_input(mut self) -> ::core::result::Result<#yyroottype, #yyerrtype> {
-                    self.end_of_input_priv().map(|r| r.0)
+                    let (root, _) = self.end_of_input_priv()?;
+                    Ok(#yyrootvalue)
                 }
             }
         } else {
             quote! {
                 pub fn new(extra: #yyextratype) -> Self {
-                    Self::new_priv(extra)
+                    Self::new_priv(extra, 0)
                 }
                 pub fn end_of_input(mut self) -> ::core::result::Result<(#yyroottype, #yyextratype), #yyerrtype> {
-                    self.end_of_input_priv()
+                    let (root, extra) = self.end_of_input_priv()?;
+                    Ok((#yyrootvalue, extra))
                 }
                 pub fn into_extra(self) -> #yyextratype {
                     self.extra
@@ -2531,18 +4703,93 @@ impl Pomelo {
                 }
             }
         };
+        //Each additional start symbol `foo_bar` gets a `Parser::new_foo_bar()` constructor, that
+        //returns a `FooBarParser` wrapping the parser, so that `end_of_input` returns its type
+        let mut new_starts = Vec::new();
+        for ((id, sp), stp) in self.extra_starts.iter().zip(&self.extra_start_states) {
+            let stateno = self.the_states.get(stp).state_num as i32;
+            let root_type = self
+                .the_symbols
+                .get(sp)
+                .data_type
+                .clone()
+                .unwrap_or_else(|| unit_type.clone());
+            let (root_type, root_value) = root_value(&root_type);
+            let new_fn = Ident::new(&format!("new_{}", id), id.span());
+            let camel = id
+                .to_string()
+                .split('_')
+                .map(|w| {
+                    let mut cs = w.chars();
+                    cs.next()
+                        .map(|c| c.to_uppercase().chain(cs).collect::<String>())
+                        .unwrap_or_default()
+                })
+                .collect::<String>();
+            let yywrapper = Ident::new(&format!("{}Parser", camel), id.span());
+            let yyvis = &yyparser.vis;
+            let yy_generics_decl = &yyparser.generics;
+
+            let impl_wrapper = if *yyextratype == unit_type {
+                new_starts.push(quote! {
+                    pub fn #new_fn() -> #yywrapper #yy_generics {
+                        #yywrapper(Self::new_priv((), #stateno))
+                    }
+                });
+                quote! {
+                    pub fn end_of_input(self) -> ::core::result::Result<#root_type, #yyerrtype> {
+                        let (root, _) = self.0.end_of_input_priv()?;
+                        Ok(#root_value)
+                    }
+                }
+            } else {
+                new_starts.push(quote! {
+                    pub fn #new_fn(extra: #yyextratype) -> #yywrapper #yy_generics {
+                        #yywrapper(Self::new_priv(extra, #stateno))
+                    }
+                });
+                quote! {
+                    pub fn end_of_input(self) -> ::core::result::Result<(#root_type, #yyextratype), #yyerrtype> {
+                        let (root, extra) = self.0.end_of_input_priv()?;
+                        Ok((#root_value, extra))
+                    }
+                    pub fn into_extra(self) -> #yyextratype {
+                        self.0.extra
+                    }
+                    pub fn extra(&self) -> &#yyextratype {
+                        &self.0.extra
+                    }
+                    pub fn extra_mut(&mut self) -> &mut #yyextratype {
+                        &mut self.0.extra
+                    }
+                }
+            };
+            src.extend(quote! {
+                #yyvis struct #yywrapper #yy_generics_decl (Parser #yy_generics) #yy_generics_where;
+
+                impl #yy_generics_impl #yywrapper #yy_generics #yy_generics_where
+                {
+                    #impl_wrapper
+                    pub fn parse(&mut self, token: Token #yy_generics_token) -> ::core::result::Result<(), #yyerrtype> {
+                        self.0.parse(token)
+                    }
+                }
+            });
+        }
+
         src.extend(quote!{
             impl #yy_generics_impl Parser #yy_generics #yy_generics_where
             {
                 #impl_parser
+                #(#new_starts)*
                 pub fn parse(&mut self, token: Token #yy_generics_token) -> ::core::result::Result<(), #yyerrtype> {
                     let (a, b) = token_value(token);
                     yy_parse_token(self, a, b)
                 }
-                fn new_priv(extra: #yyextratype) -> Self {
+                fn new_priv(extra: #yyextratype, stateno: i32) -> Self {
                     let mut yystack = YYStack::new();
                     yystack.push(YYStackEntry {
-                            stateno: 0,
+                            stateno,
                             major: 0,
                             minor: YYMinorType::YY0(())
                     });
@@ -2551,15 +4798,21 @@ impl Pomelo {
                         yystack,
                         extra,
                         yystatus: YYStatus::Normal,
+                        #yyglrinit
                     }
                 }
-                fn end_of_input_priv(mut self) -> ::core::result::Result<(#yyroottype, #yyextratype), #yyerrtype> {
+                fn end_of_input_priv(mut self) -> ::core::result::Result<(#yyaccepttype, #yyextratype), #yyerrtype> {
                     yy_parse_token(&mut self, 0, YYMinorType::YY0(()))?;
                     Ok((self.yystatus.unwrap(), self.extra))
                 }
             }
         });
 
+        let (yyparsetoken, yyclearglr) = if self.glr {
+            (quote!(yy_glr_parse_token), quote!(yy_glr_clear(yy);))
+        } else {
+            (quote!(yy_parse_token_2), quote!())
+        };
         src.extend(quote!{
             fn yy_parse_token #yy_generics_impl(yy: &mut Parser #yy_generics,
                                                         yymajor: i32, yyminor: YYMinorType #yy_generics) -> ::core::result::Result<(), #yyerrtype>
@@ -2568,9 +4821,13 @@ impl Pomelo {
                 if !yy.yystatus.is_normal() {
                     panic!("Cannot call parse after failure");
                 }
-                let res = yy_parse_token_2(yy, yymajor, yyminor);
+                let res = #yyparsetoken(yy, yymajor, yyminor);
                 if res.is_err() {
                     yy.yystatus = YYStatus::Failed;
+                    #yyclearglr
+                    while !yy.yystack.is_empty() {
+                        yy_pop_parser_stack(yy);
+                    }
                 }
                 res
             }
@@ -2580,7 +4837,7 @@ impl Pomelo {
             {
 
                 while yy.yystatus.is_normal() {
-                    let yyact = yy_find_shift_action(yy, yymajor);
+                    let yyact = yy_find_shift_action(yy.yystack.last().unwrap().stateno, yymajor);
                     if yyact < YYNSTATE {
                         assert!(yymajor != 0);  /* Impossible to shift the $ token */
                         yy_shift(yy, yyact, yymajor, yyminor)?;
@@ -2588,6 +4845,12 @@ impl Pomelo {
                         break;
                     } else if yyact < YYNSTATE + YYNRULE {
                         yy_reduce(yy, yyact - YYNSTATE)?;
+                    } else if yyact >= YY_MIN_SHIFTREDUCE {
+                        /* The state is never entered, so the value pushed as state is not used */
+                        yy_shift(yy, yyact, yymajor, yyminor)?;
+                        yy.error_count = yy.error_count.saturating_sub(1);
+                        yy_reduce(yy, yyact - YY_MIN_SHIFTREDUCE)?;
+                        break;
                     } else {
                         /* A syntax error has occurred.
                          ** The response to an error depends upon whether or not the
@@ -2609,19 +4872,31 @@ impl Pomelo {
                             if yymajor == 0 { //EOI
                                 return Err(yy_parse_failed(yy));
                             }
-                            while let Some(top) = yy.yystack.last() {
-                                if top.major == YYERRORSYMBOL { break; }
+                            loop {
+                                match yy.yystack.last() {
+                                    None => {
+                                        yy_destructor(yy, yymajor, yyminor);
+                                        return Err(yy_parse_failed(yy));
+                                    }
+                                    Some(top) if top.major == YYERRORSYMBOL => {
+                                        yy_destructor(yy, yymajor, yyminor);
+                                        break;
+                                    }
+                                    _ => {}
+                                }
 
-                                let yyact = yy_find_reduce_action(yy, YYERRORSYMBOL);
+                                let yyact = yy_find_reduce_action(yy.yystack.last().unwrap().stateno, YYERRORSYMBOL);
                                 if yyact < YYNSTATE {
                                     let e = yy_syntax_error(yy, yymajor, yyminor)?;
                                     yy_shift(yy, yyact, YYERRORSYMBOL, e)?;
                                     break;
+                                } else if yyact >= YY_MIN_SHIFTREDUCE {
+                                    let e = yy_syntax_error(yy, yymajor, yyminor)?;
+                                    yy_shift(yy, yyact, YYERRORSYMBOL, e)?;
+                                    yy_reduce(yy, yyact - YY_MIN_SHIFTREDUCE)?;
+                                    break;
                                 }
-                                yy.yystack.pop().unwrap();
-                            }
-                            if yy.yystack.is_empty() {
-                                return Err(yy_parse_failed(yy));
+                                yy_pop_parser_stack(yy);
                             }
                             yy.error_count = 3;
                             break;
@@ -2640,6 +4915,8 @@ impl Pomelo {
                             }
                             if yy.error_count == 0 {
                                 yy_syntax_error(yy, yymajor, yyminor)?;
+                            } else {
+                                yy_destructor(yy, yymajor, yyminor);
                             }
                             yy.error_count = 3;
                             break;
@@ -2649,67 +4926,6 @@ impl Pomelo {
                 Ok(())
             }
 
-            /*
-             ** Find the appropriate action for a parser given the terminal
-             ** look-ahead token look_ahead.
-             */
-            fn yy_find_shift_action #yy_generics_impl(yy: &mut Parser #yy_generics, look_ahead: i32) -> i32 #yy_generics_where
-            {
-                let stateno = yy.yystack.last().unwrap().stateno;
-
-                if stateno > YY_SHIFT_COUNT {
-                    return YY_DEFAULT[stateno as usize] as i32;
-                }
-                let i = YY_SHIFT_OFST[stateno as usize] as i32;
-                if i == YY_SHIFT_USE_DFLT {
-                    return YY_DEFAULT[stateno as usize] as i32;
-                }
-                assert!(look_ahead != YYNOCODE);
-                let i = i + look_ahead;
-
-                if i < 0 || i >= YY_ACTION.len() as i32 || YY_LOOKAHEAD[i as usize] as i32 != look_ahead {
-                    if look_ahead > 0 {
-                        if (look_ahead as usize) < YY_FALLBACK.len() {
-                            let fallback = YY_FALLBACK[look_ahead as usize];
-                            if fallback != 0 {
-                                return yy_find_shift_action(yy, fallback);
-                            }
-                        }
-                        if YYWILDCARD > 0 {
-                            let j = i - look_ahead + (YYWILDCARD as i32);
-                            if j >= 0 && j < YY_ACTION.len() as i32 && YY_LOOKAHEAD[j as usize]==YYWILDCARD {
-                                return YY_ACTION[j as usize] as i32;
-                            }
-                        }
-                    }
-                    return YY_DEFAULT[stateno as usize] as i32;
-                } else {
-                    return YY_ACTION[i as usize] as i32;
-                }
-            }
-
-            /*
-             ** Find the appropriate action for a parser given the non-terminal
-             ** look-ahead token iLookAhead.
-             */
-            fn yy_find_reduce_action #yy_generics_impl(yy: &mut Parser #yy_generics, look_ahead: i32) -> i32 #yy_generics_where
-            {
-                let stateno = yy.yystack.last().unwrap().stateno;
-                if YYERRORSYMBOL != 0 && stateno > YY_REDUCE_COUNT {
-                    return YY_DEFAULT[stateno as usize] as i32;
-                }
-                assert!(stateno <= YY_REDUCE_COUNT);
-                let i = YY_REDUCE_OFST[stateno as usize] as i32;
-                assert!(i != YY_REDUCE_USE_DFLT);
-                assert!(look_ahead != YYNOCODE );
-                let i = i + look_ahead;
-                if YYERRORSYMBOL != 0 && (i < 0 || i >= YY_ACTION.len() as i32 || YY_LOOKAHEAD[i as usize] as i32 != look_ahead) {
-                    return YY_DEFAULT[stateno as usize] as i32;
-                }
-                assert!(i >= 0 && i < YY_ACTION.len() as i32);
-                assert!(YY_LOOKAHEAD[i as usize] as i32 == look_ahead);
-                return YY_ACTION[i as usize] as i32;
-            }
         });
         let ty_span = yystackoverflow.span();
         src.extend(quote_spanned!{ty_span=>
@@ -2717,7 +4933,15 @@ impl Pomelo {
                 #yy_generics_where
             {
                 if YYSTACKLIMIT != 0 && yy.yystack.len() >= YYSTACKLIMIT {
-                    let token = token_build(yymajor, yyminor);
+                    while !yy.yystack.is_empty() {
+                        yy_pop_parser_stack(yy);
+                    }
+                    let token = if yymajor < YYNTOKEN {
+                        token_build(yymajor, yyminor)
+                    } else {
+                        yy_destructor(yy, yymajor, yyminor);
+                        None
+                    };
                     let extra = &mut yy.extra;
                     return Err(#yystackoverflow);
                 }
@@ -2733,86 +4957,1125 @@ impl Pomelo {
             fn yy_parse_failed #yy_generics_impl(yy: &mut Parser #yy_generics) -> #yyerrtype
                 #yy_generics_where
             {
-                yy.yystack.clear();
+                while !yy.yystack.is_empty() {
+                    yy_pop_parser_stack(yy);
+                }
                 let extra = &mut yy.extra;
                 #yyparsefail
             }
         });
 
-        let error_ty = error_symbol.data_type.as_ref().unwrap_or(&unit_type);
-        let error_yydt = Ident::new(&format!("YY{}", error_symbol.dt_num), Span::call_site());
-        let ty_span = yysyntaxerror.span();
-        src.extend(quote_spanned!{ty_span=>
-            fn yy_syntax_error_2 #yy_generics_impl(yy: &mut Parser #yy_generics, yymajor: i32, yyminor: YYMinorType #yy_generics) -> ::core::result::Result<#error_ty, #yyerrtype>
-                #yy_generics_where
-            {
-                let token = token_build(yymajor, yyminor);
-                let extra = &mut yy.extra;
-                #yysyntaxerror
-            }
+        if self.glr {
+            self.generate_glr(&mut src, &yyparser.generics, yyerrtype, &types)?;
+        } else if let Some((id, ..)) = self.merges.first() {
+            return error_span(id.span(), "%merge is only used with %glr"); //tested
+        }
+
+        /* Generate the code to run when a value is discarded from the stack */
+        //Use quote_spanned! to inject `extra` and `token` into the `code` of the destructors
+        let mut yydestructors = Vec::new();
+        for (id, alias, code) in &self.destructors {
+            let sp = match self.symbol_find(&id.to_string()) {
+                Some(sp) => self.the_symbols.get(sp),
+                None => return error_span(id.span(), "Unknown symbol in destructor"), //tested
+            };
+            if let MultiTerminal(_) = sp.typ {
+                return error_span(id.span(), "Compound tokens cannot have a destructor");
+            }
+            let index = sp.index as i32;
+            let yydt = Ident::new(&format!("YY{}", sp.dt_num), Span::call_site());
+            let alias = match alias {
+                Some(alias) => quote!(#alias),
+                None => quote!(_),
+            };
+            let ty_span = code.span();
+            yydestructors.push(quote_spanned!(ty_span=>
+                (#index, YYMinorType::#yydt(#alias), extra) => { #code }
+            ));
+        }
+        if let Some(code) = &self.token_destructor {
+            let ty_span = code.span();
+            yydestructors.push(quote_spanned!(ty_span=>
+                (yymajor, yyminor, extra) if yymajor < YYNTOKEN => {
+                    if let Some(token) = token_build(yymajor, yyminor) { #code }
+                }
+            ));
+        }
+        src.extend(quote! {
+            fn yy_destructor #yy_generics_impl(yy: &mut Parser #yy_generics, yymajor: i32, yyminor: YYMinorType #yy_generics)
+                #yy_generics_where
+            {
+                match (yymajor, yyminor, &mut yy.extra) {
+                    #(#yydestructors)*
+                    _ => {}
+                }
+            }
+            fn yy_pop_parser_stack #yy_generics_impl(yy: &mut Parser #yy_generics)
+                #yy_generics_where
+            {
+                let yytos = yy.yystack.pop().unwrap();
+                yy_destructor(yy, yytos.major, yytos.minor);
+            }
+        });
+
+        let error_ty = error_symbol.data_type.as_ref().unwrap_or(&unit_type);
+        let error_yydt = Ident::new(&format!("YY{}", error_symbol.dt_num), Span::call_site());
+        let ty_span = yysyntaxerror.span();
+        src.extend(quote_spanned!{ty_span=>
+            fn yy_syntax_error_2 #yy_generics_impl(yy: &mut Parser #yy_generics, yymajor: i32, yyminor: YYMinorType #yy_generics) -> ::core::result::Result<#error_ty, #yyerrtype>
+                #yy_generics_where
+            {
+                let token = token_build(yymajor, yyminor);
+                let extra = &mut yy.extra;
+                #yysyntaxerror
+            }
             fn yy_syntax_error #yy_generics_impl(yy: &mut Parser #yy_generics, yymajor: i32, yyminor: YYMinorType #yy_generics) -> ::core::result::Result<YYMinorType #yy_generics, #yyerrtype>
                 #yy_generics_where
             {
-                let e = yy_syntax_error_2(yy, yymajor, yyminor)?;
-                Ok(YYMinorType::#error_yydt(e))
+                let e = yy_syntax_error_2(yy, yymajor, yyminor)?;
+                Ok(YYMinorType::#error_yydt(e))
+            }
+        });
+
+        /* Generate a function with the code executed by each REDUCE action.
+         ** Rules that translate to the same code share the function, and yy_reduce
+         ** calls them through a table indexed by the rule number.
+         */
+        let mut yyactions = Vec::new();
+        let mut yyaction_names = HashMap::new();
+        let mut yyrules = Vec::new();
+        for rp in &self.rules {
+            let rp = self.the_rules.get(rp);
+            let code = self.translate_code(&rp)?;
+            let key = code.to_string();
+            if let Some(yyaction) = yyaction_names.get(&key) {
+                yyrules.push(Ident::clone(yyaction));
+                continue;
+            }
+            let yyaction = Ident::new(&format!("yy_action_{}", rp.index), Span::call_site());
+            //Use quote_spanned! to inject `extra` into the `code` rule
+            let ty_span = rp.code.span();
+            let extra = quote_spanned!(ty_span=> extra);
+            yyactions.push(quote!(
+                fn #yyaction #yy_generics_impl(yy: &mut Parser #yy_generics) -> ::core::result::Result<YYMinorType #yy_generics, #yyerrtype>
+                    #yy_generics_where
+                {
+                    let #extra = &mut yy.extra;
+                    Ok({ #code })
+                }
+            ));
+            yyaction_names.insert(key, yyaction.clone());
+            yyrules.push(yyaction);
+        }
+        let yynrule = self.rules.len();
+        src.extend(quote!(
+            #(#yyactions)*
+
+            impl #yy_generics_impl Parser #yy_generics #yy_generics_where
+            {
+                const YY_ACTIONS: [fn(&mut Self) -> ::core::result::Result<YYMinorType #yy_generics, #yyerrtype>; #yynrule] = [
+                    #(#yyrules),*
+                ];
+            }
+        ));
+
+        let accept_code = if self.glr {
+            quote! {
+                yy.yystatus = YYStatus::Accepted(vec![yygotominor]);
+                yy.yystack.clear();
+            }
+        } else {
+            quote! {
+                yy.yystatus = YYStatus::Accepted(yygotominor);
+                yy.yystack.clear();
+            }
+        };
+        let yyreduce_fn = quote!(
+            fn yy_reduce #yy_generics_impl(yy: &mut Parser #yy_generics, mut yyruleno: i32) -> ::core::result::Result<(), #yyerrtype>
+                #yy_generics_where
+            {
+                loop {
+                    let yyactions: &[_] = &<Parser #yy_generics>::YY_ACTIONS;
+                    let yygotominor = yyactions[yyruleno as usize](yy)?;
+                    let yygoto = YY_RULE_INFO[yyruleno as usize] as i32;
+                    let yyact = yy_find_reduce_action(yy.yystack.last().unwrap().stateno, yygoto);
+                    if yyact < YYNSTATE {
+                        yy_shift(yy, yyact, yygoto, yygotominor)?;
+                        return Ok(());
+                    } else if yyact >= YY_MIN_SHIFTREDUCE {
+                        /* The goto is a SHIFTREDUCE too, so reduce again */
+                        yy_shift(yy, yyact, yygoto, yygotominor)?;
+                        yyruleno = yyact - YY_MIN_SHIFTREDUCE;
+                    } else {
+                        assert!(yyact == YYNSTATE + YYNRULE + 1);
+                        #accept_code
+                        return Ok(());
+                    }
+                }
+            }
+        );
+        yyreduce_fn.to_tokens(&mut src);
+
+        Ok(src)
+    }
+
+    /* Generate Lemon's compressed action tables, and the functions that find an action in them */
+    fn generate_compressed_tables(
+        &self,
+        src: &mut TokenStream,
+        yycodetype: &Ident,
+        yyactiontype: &Ident,
+    ) {
+        /* Generate the action table and its associates:
+         **
+         **  yy_action[]        A single table containing all actions.
+         **  yy_lookahead[]     A table containing the lookahead for each entry in
+         **                     yy_action.  Used to detect hash collisions.
+         **  yy_shift_ofst[]    For each state, the offset into yy_action for
+         **                     shifting terminals.
+         **  yy_reduce_ofst[]   For each state, the offset into yy_action for
+         **                     shifting non-terminals after a reduce.
+         **  yy_default[]       Default action for each state.
+         */
+
+        let mut ax = Vec::with_capacity(2 * self.states.len());
+        /* Compute the actions on all states and count them up */
+        for stp in &self.states {
+            ax.push(AxSet {
+                stp: *stp,
+                is_tkn: true,
+                n_action: self.the_states.get(stp).n_tkn_act,
+            });
+            ax.push(AxSet {
+                stp: *stp,
+                is_tkn: false,
+                n_action: self.the_states.get(stp).n_nt_act,
+            });
+        }
+
+        ax.sort_by_key(|a| a.n_action);
+        ax.reverse();
+
+        let mut max_tkn_ofst = 0;
+        let mut min_tkn_ofst = 0;
+        let mut max_nt_ofst = 0;
+        let mut min_nt_ofst = 0;
+
+        /* Compute the action table.  In order to try to keep the size of the
+         ** action table to a minimum, the heuristic of placing the largest action
+         ** sets first is used.
+         */
+        let mut acttab = ActTab::new();
+
+        for a in &ax {
+            let mut actset = ActionSet::new();
+
+            if a.n_action == 0 {
+                continue;
+            }
+            if a.is_tkn {
+                for ap in &self.the_states.get(a.stp).actions {
+                    let ap = ap.borrow();
+                    let sp = self.the_symbols.get(ap.look_ahead);
+                    if sp.index >= self.num_terminals {
+                        continue;
+                    }
+                    match self.compute_action(&ap) {
+                        None => continue,
+                        Some(action) => actset.add_action(sp.index, action),
+                    }
+                }
+                let ofs = acttab.insert_action_set(&actset);
+                let mut stp = self.the_states.get_mut(a.stp);
+                stp.i_tkn_ofst = Some(ofs);
+                min_tkn_ofst = cmp::min(ofs, min_tkn_ofst);
+                max_tkn_ofst = cmp::max(ofs, max_tkn_ofst);
+            } else {
+                for ap in &self.the_states.get(a.stp).actions {
+                    let ap = ap.borrow();
+                    let sp = self.the_symbols.get(ap.look_ahead);
+                    if sp.index < self.num_terminals {
+                        continue;
+                    }
+                    if sp.index == self.default_index {
+                        continue;
+                    }
+                    //sp is a non-default NonTerminal
+                    match self.compute_action(&ap) {
+                        None => continue,
+                        Some(action) => actset.add_action(sp.index, action),
+                    }
+                }
+                let ofs = acttab.insert_action_set(&actset);
+                let mut stp = self.the_states.get_mut(a.stp);
+                stp.i_nt_ofst = Some(ofs);
+                min_nt_ofst = cmp::min(ofs, min_nt_ofst);
+                max_nt_ofst = cmp::max(ofs, max_nt_ofst);
+            }
+        }
+        /* Output the yy_action table */
+        let yy_action = acttab
+            .a_action
+            .iter()
+            .map(|ac| match ac {
+                None => (self.states.len() + self.rules.len() + 2) as i32,
+                Some(a) => a.action as i32,
+            })
+            .map(Literal::i32_unsuffixed);
+        let yy_action_len = yy_action.len();
+        src.extend(quote!(static YY_ACTION: [i32; #yy_action_len] = [ #(#yy_action),* ];));
+
+        /* Output the yy_lookahead table */
+        let yy_lookahead = acttab
+            .a_action
+            .iter()
+            .map(|ac| match ac {
+                None => self.default_index,
+                Some(a) => a.lookahead,
+            })
+            .map(Literal::usize_unsuffixed);
+        let yy_lookahead_len = yy_lookahead.len();
+        src.extend(
+            quote!(static YY_LOOKAHEAD: [#yycodetype; #yy_lookahead_len] = [ #(#yy_lookahead),* ];),
+        );
+
+        /* Output the yy_shift_ofst[] table */
+        let n = self
+            .states
+            .iter()
+            .rposition(|st| self.the_states.get(st).i_tkn_ofst.is_some())
+            .unwrap();
+        let yy_shift_use_dflt = min_tkn_ofst - 1;
+        src.extend(quote!(const YY_SHIFT_USE_DFLT: i32 = #yy_shift_use_dflt;));
+        src.extend(quote!(const YY_SHIFT_COUNT: i32 = #n as i32;));
+        src.extend(quote!(const YY_SHIFT_MIN: i32 = #min_tkn_ofst;));
+        src.extend(quote!(const YY_SHIFT_MAX: i32 = #max_tkn_ofst;));
+        let yy_shift_ofst_type = minimum_signed_type(max_tkn_ofst as usize);
+        let yy_shift_ofst = self.states[0..=n]
+            .iter()
+            .map(|stp| {
+                let stp = self.the_states.get(stp);
+                stp.i_tkn_ofst.unwrap_or(min_tkn_ofst - 1)
+            })
+            .map(Literal::i32_unsuffixed);
+        let yy_shift_ofst_len = yy_shift_ofst.len();
+        src.extend(quote!(static YY_SHIFT_OFST: [#yy_shift_ofst_type; #yy_shift_ofst_len] = [ #(#yy_shift_ofst),* ];));
+
+        /* Output the yy_reduce_ofst[] table */
+        let n = self
+            .states
+            .iter()
+            .rposition(|st| self.the_states.get(st).i_nt_ofst.is_some())
+            .unwrap();
+        let yy_reduce_use_dflt = min_nt_ofst - 1;
+        src.extend(quote!(const YY_REDUCE_USE_DFLT: i32 = #yy_reduce_use_dflt;));
+        src.extend(quote!(const YY_REDUCE_COUNT: i32 = #n as i32;));
+        src.extend(quote!(const YY_REDUCE_MIN: i32 = #min_nt_ofst;));
+        src.extend(quote!(const YY_REDUCE_MAX: i32 = #max_nt_ofst;));
+        let yy_reduce_ofst_type = minimum_signed_type(max_nt_ofst as usize);
+        let yy_reduce_ofst = self.states[0..=n]
+            .iter()
+            .map(|stp| {
+                let stp = self.the_states.get(stp);
+                stp.i_nt_ofst.unwrap_or(min_nt_ofst - 1)
+            })
+            .map(Literal::i32_unsuffixed);
+        let yy_reduce_ofst_len = yy_reduce_ofst.len();
+        src.extend(quote!(static YY_REDUCE_OFST: [#yy_reduce_ofst_type; #yy_reduce_ofst_len] = [ #(#yy_reduce_ofst),* ];));
+
+        let yy_default = self
+            .states
+            .iter()
+            .map(|stp| self.the_states.get(stp).i_dflt)
+            .map(Literal::usize_unsuffixed);
+        let yy_default_len = yy_default.len();
+        src.extend(
+            quote!(static YY_DEFAULT: [#yyactiontype; #yy_default_len] = [ #(#yy_default),* ];),
+        );
+
+        src.extend(quote! {
+            /*
+             ** Find the appropriate action for a parser given the terminal
+             ** look-ahead token look_ahead.
+             */
+            fn yy_find_shift_action(stateno: i32, look_ahead: i32) -> i32 {
+
+                if stateno > YY_SHIFT_COUNT {
+                    return YY_DEFAULT[stateno as usize] as i32;
+                }
+                let i = YY_SHIFT_OFST[stateno as usize] as i32;
+                if i == YY_SHIFT_USE_DFLT {
+                    return YY_DEFAULT[stateno as usize] as i32;
+                }
+                assert!(look_ahead != YYNOCODE);
+                let i = i + look_ahead;
+
+                if i < 0 || i >= YY_ACTION.len() as i32 || YY_LOOKAHEAD[i as usize] as i32 != look_ahead {
+                    if look_ahead > 0 {
+                        if (look_ahead as usize) < YY_FALLBACK.len() {
+                            let fallback = YY_FALLBACK[look_ahead as usize];
+                            if fallback != 0 {
+                                return yy_find_shift_action(stateno, fallback);
+                            }
+                        }
+                        if YYWILDCARD > 0 {
+                            let j = i - look_ahead + (YYWILDCARD as i32);
+                            if j >= 0 && j < YY_ACTION.len() as i32 && YY_LOOKAHEAD[j as usize]==YYWILDCARD {
+                                return YY_ACTION[j as usize] as i32;
+                            }
+                        }
+                    }
+                    return YY_DEFAULT[stateno as usize] as i32;
+                } else {
+                    return YY_ACTION[i as usize] as i32;
+                }
+            }
+
+            /*
+             ** Find the appropriate action for a parser given the non-terminal
+             ** look-ahead token iLookAhead.
+             */
+            fn yy_find_reduce_action(stateno: i32, look_ahead: i32) -> i32 {
+                if YYERRORSYMBOL != 0 && stateno > YY_REDUCE_COUNT {
+                    return YY_DEFAULT[stateno as usize] as i32;
+                }
+                assert!(stateno <= YY_REDUCE_COUNT);
+                let i = YY_REDUCE_OFST[stateno as usize] as i32;
+                assert!(i != YY_REDUCE_USE_DFLT);
+                assert!(look_ahead != YYNOCODE );
+                let i = i + look_ahead;
+                if YYERRORSYMBOL != 0 && (i < 0 || i >= YY_ACTION.len() as i32 || YY_LOOKAHEAD[i as usize] as i32 != look_ahead) {
+                    return YY_DEFAULT[stateno as usize] as i32;
+                }
+                assert!(i >= 0 && i < YY_ACTION.len() as i32);
+                assert!(YY_LOOKAHEAD[i as usize] as i32 == look_ahead);
+                return YY_ACTION[i as usize] as i32;
+            }
+        });
+    }
+
+    /* Generate a dense action table, with a row for each state and a column for each symbol.
+     ** The fallback tokens and the wildcard are resolved here, so finding an action is just
+     ** an indexing operation.
+     */
+    fn generate_dense_tables(&self, src: &mut TokenStream, yyactiontype: &Ident) {
+        let wildcard = self.wildcard.map(|w| self.the_symbols.get(w).index);
+        let yy_dense_action = self.states.iter().map(|stp| {
+            let stp = self.the_states.get(stp);
+            let mut actions = vec![None; self.default_index];
+            for ap in &stp.actions {
+                let ap = ap.borrow();
+                let index = self.the_symbols.get(ap.look_ahead).index;
+                if index < self.default_index {
+                    if let Some(action) = self.compute_action(&ap) {
+                        actions[index] = Some(action);
+                    }
+                }
+            }
+            let row = (0..self.default_index).map(|mut index| {
+                //A token without an action uses that of its fallback, or else that of the wildcard
+                if index < self.num_terminals {
+                    while actions[index].is_none() {
+                        match self.the_symbols.get(self.symbols[index]).fallback {
+                            Some(fb) => index = self.the_symbols.get(fb).index,
+                            None => break,
+                        }
+                    }
+                    if let (None, Some(w)) = (actions[index], wildcard) {
+                        if index > 0 {
+                            index = w;
+                        }
+                    }
+                }
+                let action = actions[index].unwrap_or(stp.i_dflt);
+                Literal::usize_unsuffixed(action)
+            });
+            quote!([ #(#row),* ])
+        });
+        let yy_dense_width = self.default_index;
+        let yy_dense_len = self.states.len();
+        src.extend(quote! {
+            static YY_DENSE_ACTION: [[#yyactiontype; #yy_dense_width]; #yy_dense_len] = [ #(#yy_dense_action),* ];
+
+            /*
+             ** Find the appropriate action for a parser given the terminal
+             ** look-ahead token look_ahead.
+             */
+            fn yy_find_shift_action(stateno: i32, look_ahead: i32) -> i32 {
+                YY_DENSE_ACTION[stateno as usize][look_ahead as usize] as i32
+            }
+
+            /*
+             ** Find the appropriate action for a parser given the non-terminal
+             ** look-ahead token look_ahead.
+             */
+            fn yy_find_reduce_action(stateno: i32, look_ahead: i32) -> i32 {
+                YY_DENSE_ACTION[stateno as usize][look_ahead as usize] as i32
+            }
+        });
+    }
+
+    /* Generate the runtime of a %glr parser. While there is no conflict it is a LR parser, but a
+     ** conflict splits the stack into a graph-structured stack, as in Tomita's algorithm: the
+     ** branches share the nodes below them, and the nodes for the same state at the same
+     ** position of the input are merged. The code of the rules is deferred while the parser is
+     ** split: each symbol keeps the ways it was reduced, and they are run when a single branch is
+     ** left, once for each way.
+     */
+    fn generate_glr(
+        &self,
+        src: &mut TokenStream,
+        generics: &syn::Generics,
+        yyerrtype: &Type,
+        types: &HashMap<Type, usize>,
+    ) -> syn::Result<()> {
+        let (yy_generics_impl, yy_generics, yy_generics_where) = generics.split_for_impl();
+
+        /* The actions hidden by the conflicts, sorted by state and look-ahead */
+        let mut yy_glr_actions = Vec::new();
+        for stp in &self.states {
+            let stp = self.the_states.get(stp);
+            for ap in &stp.actions {
+                let ap = ap.borrow();
+                if let ActionDetail::SRConflict(rp) | ActionDetail::RRConflict(rp) = ap.detail {
+                    let la = self.the_symbols.get(ap.look_ahead).index as i32;
+                    let act = (self.the_rules.get(rp).index + self.states.len()) as i32;
+                    yy_glr_actions.push((stp.state_num as i32, la, act));
+                }
+            }
+        }
+        yy_glr_actions.sort_unstable();
+        let yy_glr_actions_len = yy_glr_actions.len();
+        let yy_glr_actions = yy_glr_actions.into_iter().map(|(st, la, act)| {
+            let (st, la, act) = (
+                Literal::i32_unsuffixed(st),
+                Literal::i32_unsuffixed(la),
+                Literal::i32_unsuffixed(act),
+            );
+            quote!((#st, #la, #act))
+        });
+
+        /* The number of symbols in the right-hand side of each rule, the length of its paths */
+        let yy_rule_nrhs = self
+            .rules
+            .iter()
+            .map(|rp| self.the_rules.get(rp).rhs.len())
+            .map(Literal::usize_unsuffixed);
+        let yy_rule_nrhs_len = yy_rule_nrhs.len();
+
+        /* The %merge code of each symbol, with `extra` injected as in the destructors */
+        let mut yymerges = Vec::new();
+        for (id, a, b, code) in &self.merges {
+            let sp = match self.symbol_find(&id.to_string()) {
+                Some(sp) => sp,
+                None => return error_span(id.span(), "Unknown symbol in merge"),
+            };
+            let s = self.the_symbols.get(sp);
+            if !matches!(s.typ, NonTerminal { .. }) {
+                return error_span(id.span(), "Merge symbol must be a non-terminal");
+                //tested
+            }
+            //An additional start symbol is accepted through its augmented `S'`
+            let mut indices = vec![s.index as i32];
+            for (_, sym_l) in self.extra_starts.iter().filter(|(x, _)| x == id) {
+                indices.push(self.the_symbols.get(sym_l).index as i32);
+            }
+            let yydt = Ident::new(&format!("YY{}", s.dt_num), Span::call_site());
+            let ty_span = code.span();
+            for index in indices {
+                yymerges.push(quote_spanned!(ty_span=>
+                    (#index, YYMinorType::#yydt(#a), YYMinorType::#yydt(#b), extra) => Ok(YYMinorType::#yydt(#code)),
+                ));
+            }
+        }
+
+        /* A value is cloned only if its type implements Clone, the others are moved */
+        let mut dt_nums = types.values().collect::<Vec<_>>();
+        dt_nums.sort();
+        let yyclones = dt_nums.into_iter().map(|n| {
+            let yydt = Ident::new(&format!("YY{}", n), Span::call_site());
+            quote!(YYMinorType::#yydt(v) => (&YYCloneProbe(v)).yy_clone().map(YYMinorType::#yydt),)
+        });
+
+        src.extend(quote! {
+            static YY_GLR_ACTIONS: [(i32, i32, i32); #yy_glr_actions_len] = [ #(#yy_glr_actions),* ];
+            static YY_RULE_NRHS: [usize; #yy_rule_nrhs_len] = [ #(#yy_rule_nrhs),* ];
+
+            /*
+             ** Find the actions of a state that are hidden by a conflict.
+             */
+            fn yy_find_glr_actions(stateno: i32, look_ahead: i32) -> impl Iterator<Item = i32> {
+                let start = YY_GLR_ACTIONS.partition_point(|&(st, la, _)| (st, la) < (stateno, look_ahead));
+                YY_GLR_ACTIONS[start..]
+                    .iter()
+                    .take_while(move |&&(st, la, _)| st == stateno && la == look_ahead)
+                    .map(|&(_, _, act)| act)
+            }
+
+            /*
+             ** Find every action of a state for the look-ahead token, including the hidden ones.
+             */
+            fn yy_find_all_actions(stateno: i32, look_ahead: i32) -> impl Iterator<Item = i32> {
+                ::core::iter::once(yy_find_shift_action(stateno, look_ahead))
+                    .chain(yy_find_glr_actions(stateno, look_ahead))
+            }
+
+            fn yy_merge #yy_generics_impl(yy: &mut Parser #yy_generics, yymajor: i32, a: YYMinorType #yy_generics, b: YYMinorType #yy_generics)
+                -> ::core::result::Result<YYMinorType #yy_generics, (YYMinorType #yy_generics, YYMinorType #yy_generics)>
+                #yy_generics_where
+            {
+                match (yymajor, a, b, &mut yy.extra) {
+                    #(#yymerges)*
+                    (_, a, b, _) => Err((a, b)),
+                }
             }
-        });
 
-        /* Generate code which execution during each REDUCE action */
-        /* First output rules other than the default: rule */
-        //TODO avoid dumping the same code twice
-        let mut yyrules = Vec::new();
-        for rp in &self.rules {
-            let rp = self.the_rules.get(rp);
-            let code = self.translate_code(&rp)?;
-            let index = rp.index as i32;
+            /*
+             ** `(&YYCloneProbe(v)).yy_clone()` clones `v` if its type implements Clone, and
+             ** returns None otherwise: the method of `YYClone` is found first, when it exists.
+             */
+            struct YYCloneProbe<'a, T>(&'a T);
+            trait YYClone<T> {
+                fn yy_clone(&self) -> Option<T>;
+            }
+            impl<T: Clone> YYClone<T> for YYCloneProbe<'_, T> {
+                fn yy_clone(&self) -> Option<T> {
+                    Some(self.0.clone())
+                }
+            }
+            trait YYNoClone<T> {
+                fn yy_clone(&self) -> Option<T>;
+            }
+            impl<T> YYNoClone<T> for &YYCloneProbe<'_, T> {
+                fn yy_clone(&self) -> Option<T> {
+                    None
+                }
+            }
+            fn yy_clone #yy_generics_impl(yyminor: &YYMinorType #yy_generics) -> Option<YYMinorType #yy_generics>
+                #yy_generics_where
+            {
+                match yyminor {
+                    YYMinorType::YY0(()) => Some(YYMinorType::YY0(())),
+                    #(#yyclones)*
+                    _ => None,
+                }
+            }
 
-            //Use quote_spanned! to inject `extra` into the `code` rule
-            let ty_span = rp.code.span();
-            yyrules.push(quote_spanned!(ty_span=> (#index, extra) => { #code }));
-        }
-        yyrules.push(quote!(_ => unreachable!("no rule to apply")));
+            /* A node of the graph-structured stack: a state at a position of the input, with a link
+             ** to each node below it and the symbol between them */
+            struct YYGlrNode #yy_generics_impl #yy_generics_where
+            {
+                stateno: i32,
+                links: ::core::cell::RefCell<std::vec::Vec<(std::rc::Rc<YYGlrNode #yy_generics>, std::rc::Rc<YYGlrSym #yy_generics>)>>,
+            }
+
+            /* A symbol of the graph. While the parser is split, the value of a non-terminal is
+             ** deferred: it keeps the rule and the symbols of each way it was reduced */
+            struct YYGlrSym #yy_generics_impl #yy_generics_where
+            {
+                major: i32,
+                value: ::core::cell::RefCell<YYGlrValue #yy_generics>,
+            }
+
+            enum YYGlrValue #yy_generics_impl #yy_generics_where
+            {
+                Values(std::vec::Vec<YYMinorType #yy_generics>),
+                Deferred(std::vec::Vec<(i32, std::vec::Vec<std::rc::Rc<YYGlrSym #yy_generics>>)>),
+            }
+
+            /* The work done for a token before it is shifted */
+            struct YYGlrStep #yy_generics_impl #yy_generics_where
+            {
+                frontier: std::vec::Vec<std::rc::Rc<YYGlrNode #yy_generics>>,    /* The nodes at this position */
+                reduces: std::vec::Vec<(std::rc::Rc<YYGlrNode #yy_generics>, i32, Option<std::rc::Rc<YYGlrSym #yy_generics>>)>,
+                                                                                /* The rules to reduce from a node,
+                                                                                 ** through the link of a symbol */
+                shifts: std::vec::Vec<(std::rc::Rc<YYGlrNode #yy_generics>, i32)>, /* The nodes that shift the token */
+                accepted: Option<std::rc::Rc<YYGlrSym #yy_generics>>,            /* The start symbol, at the end */
+            }
+
+            fn yy_glr_sym #yy_generics_impl(major: i32, value: YYGlrValue #yy_generics) -> std::rc::Rc<YYGlrSym #yy_generics>
+                #yy_generics_where
+            {
+                std::rc::Rc::new(YYGlrSym { major, value: ::core::cell::RefCell::new(value) })
+            }
+
+            fn yy_glr_node #yy_generics_impl(stateno: i32, links: std::vec::Vec<(std::rc::Rc<YYGlrNode #yy_generics>, std::rc::Rc<YYGlrSym #yy_generics>)>)
+                -> std::rc::Rc<YYGlrNode #yy_generics>
+                #yy_generics_where
+            {
+                std::rc::Rc::new(YYGlrNode { stateno, links: ::core::cell::RefCell::new(links) })
+            }
+
+            /*
+             ** Take the values of a symbol.
+             */
+            fn yy_glr_take #yy_generics_impl(sym: &YYGlrSym #yy_generics) -> std::vec::Vec<YYMinorType #yy_generics>
+                #yy_generics_where
+            {
+                match &mut *sym.value.borrow_mut() {
+                    YYGlrValue::Values(values) => ::core::mem::take(values),
+                    YYGlrValue::Deferred(_) => std::vec::Vec::new(),
+                }
+            }
 
-        let accept_code = match types.get(&yyroottype) {
-            Some(n) => {
-                let yyroot = Ident::new(&format!("YY{}", n), Span::call_site());
-                quote!(
-                    if let YYMinorType::#yyroot(root) = yygotominor {
-                        yy.yystatus = YYStatus::Accepted(root);
-                        yy.yystack.clear();
+            /*
+             ** Drop the nodes and the symbols that are not used anymore, running the destructors
+             ** of their values. The deferred values are never built, so they need no destructor.
+             */
+            fn yy_glr_release #yy_generics_impl(yy: &mut Parser #yy_generics,
+                                                mut nodes: std::vec::Vec<std::rc::Rc<YYGlrNode #yy_generics>>,
+                                                mut syms: std::vec::Vec<std::rc::Rc<YYGlrSym #yy_generics>>)
+                #yy_generics_where
+            {
+                loop {
+                    if let Some(node) = nodes.pop() {
+                        if let Ok(node) = std::rc::Rc::try_unwrap(node) {
+                            for (prev, sym) in node.links.into_inner() {
+                                nodes.push(prev);
+                                syms.push(sym);
+                            }
+                        }
+                    } else if let Some(sym) = syms.pop() {
+                        if let Ok(sym) = std::rc::Rc::try_unwrap(sym) {
+                            match sym.value.into_inner() {
+                                YYGlrValue::Values(values) => {
+                                    for value in values {
+                                        yy_destructor(yy, sym.major, value);
+                                    }
+                                }
+                                YYGlrValue::Deferred(alts) => {
+                                    for (_, children) in alts {
+                                        syms.extend(children);
+                                    }
+                                }
+                            }
+                        }
                     } else {
-                        unreachable!("unexpected root type");
+                        break;
                     }
-                )
+                }
             }
-            None => quote!(
-                yy.yystatus = YYStatus::Accepted(());
-                yy.yystack.clear();
-            ),
-        };
 
-        let yyreduce_fn = quote!(
-            fn yy_reduce #yy_generics_impl(yy: &mut Parser #yy_generics, yyruleno: i32) -> ::core::result::Result<(), #yyerrtype>
+            /*
+             ** Drop the graph-structured stack, the parser is back to its LR stack.
+             */
+            fn yy_glr_clear #yy_generics_impl(yy: &mut Parser #yy_generics)
+                #yy_generics_where
+            {
+                let mut nodes = ::core::mem::take(&mut yy.yyglr);
+                nodes.extend(yy.yyglrbase.take());
+                yy_glr_release(yy, nodes, std::vec::Vec::new());
+            }
+
+            /*
+             ** The base of the graph is the top of the LR stack. A path that goes below it takes
+             ** the top of the LR stack into the graph.
+             */
+            fn yy_glr_lift #yy_generics_impl(yy: &mut Parser #yy_generics, node: &std::rc::Rc<YYGlrNode #yy_generics>)
+                #yy_generics_where
+            {
+                let is_base = matches!(&yy.yyglrbase, Some(base) if std::rc::Rc::ptr_eq(base, node));
+                if is_base && yy.yystack.len() > 1 {
+                    let top = yy.yystack.pop().unwrap();
+                    let below = yy_glr_node(yy.yystack.last().unwrap().stateno, std::vec::Vec::new());
+                    let sym = yy_glr_sym(top.major, YYGlrValue::Values(vec![top.minor]));
+                    node.links.borrow_mut().push((below.clone(), sym));
+                    yy.yyglrbase = Some(below);
+                }
+            }
+
+            /*
+             ** Find the paths of `n` symbols down from a node, that go through the link of `via`
+             ** if given. Each path is returned with its symbols from left to right, and the node
+             ** where it ends.
+             */
+            fn yy_glr_paths #yy_generics_impl(yy: &mut Parser #yy_generics, node: &std::rc::Rc<YYGlrNode #yy_generics>,
+                                            n: usize, via: Option<&std::rc::Rc<YYGlrSym #yy_generics>>)
+                -> std::vec::Vec<(std::vec::Vec<std::rc::Rc<YYGlrSym #yy_generics>>, std::rc::Rc<YYGlrNode #yy_generics>)>
+                #yy_generics_where
+            {
+                let mut paths = std::vec::Vec::new();
+                let mut pending = vec![(node.clone(), std::vec::Vec::new(), via.is_none())];
+                while let Some((node, mut syms, used)) = pending.pop() {
+                    if syms.len() == n {
+                        if used {
+                            syms.reverse();
+                            paths.push((syms, node));
+                        }
+                        continue;
+                    }
+                    yy_glr_lift(yy, &node);
+                    for (prev, sym) in node.links.borrow().iter().rev() {
+                        let used = used || matches!(via, Some(via) if std::rc::Rc::ptr_eq(via, sym));
+                        let mut syms = syms.clone();
+                        syms.push(sym.clone());
+                        pending.push((prev.clone(), syms, used));
+                    }
+                }
+                paths
+            }
+
+            /*
+             ** The path down from a node to the base of the graph, with the state and the symbol of
+             ** each link from the bottom. If `linear` it must be the only one, else the first one is
+             ** taken.
+             */
+            fn yy_glr_path #yy_generics_impl(yy: &Parser #yy_generics, node: &std::rc::Rc<YYGlrNode #yy_generics>, linear: bool)
+                -> Option<std::vec::Vec<(i32, std::rc::Rc<YYGlrSym #yy_generics>)>>
+                #yy_generics_where
+            {
+                let base = yy.yyglrbase.as_ref().unwrap();
+                let mut path = std::vec::Vec::new();
+                let mut node = node.clone();
+                while !std::rc::Rc::ptr_eq(&node, base) {
+                    let prev = {
+                        let links = node.links.borrow();
+                        if linear && links.len() > 1 {
+                            return None;
+                        }
+                        let (prev, sym) = &links[0];
+                        path.push((node.stateno, sym.clone()));
+                        prev.clone()
+                    };
+                    node = prev;
+                }
+                path.reverse();
+                Some(path)
+            }
+
+            /*
+             ** Schedule the actions of a node for the look-ahead token.
+             */
+            fn yy_glr_schedule #yy_generics_impl(step: &mut YYGlrStep #yy_generics, node: &std::rc::Rc<YYGlrNode #yy_generics>, yymajor: i32)
+                #yy_generics_where
+            {
+                for yyact in yy_find_all_actions(node.stateno, yymajor) {
+                    if yyact < YYNSTATE {
+                        step.shifts.push((node.clone(), yyact));
+                    } else if yyact < YYNSTATE + YYNRULE {
+                        step.reduces.push((node.clone(), yyact - YYNSTATE, None));
+                    }
+                }
+            }
+
+            /*
+             ** Add another way to reduce a symbol, unless it is already there.
+             */
+            fn yy_glr_add_alt #yy_generics_impl(sym: &YYGlrSym #yy_generics, yyruleno: i32, syms: std::vec::Vec<std::rc::Rc<YYGlrSym #yy_generics>>)
+                #yy_generics_where
+            {
+                if let YYGlrValue::Deferred(alts) = &mut *sym.value.borrow_mut() {
+                    let same = |(rule, other): &(i32, std::vec::Vec<std::rc::Rc<YYGlrSym #yy_generics>>)| {
+                        *rule == yyruleno && other.iter().zip(&syms).all(|(a, b)| std::rc::Rc::ptr_eq(a, b))
+                    };
+                    if !alts.iter().any(same) {
+                        alts.push((yyruleno, syms));
+                    }
+                }
+            }
+
+            /*
+             ** Reduce a rule from a node, by every path through the link of `via` if given. The
+             ** new symbol links the node where the path ends to the node for the goto state, that
+             ** is shared by all the reductions to the same state.
+             */
+            fn yy_glr_reduce #yy_generics_impl(yy: &mut Parser #yy_generics, step: &mut YYGlrStep #yy_generics, yymajor: i32,
+                                            node: std::rc::Rc<YYGlrNode #yy_generics>, yyruleno: i32,
+                                            via: Option<std::rc::Rc<YYGlrSym #yy_generics>>)
                 #yy_generics_where
             {
-                let yygotominor: YYMinorType #yy_generics = match (yyruleno, &mut yy.extra) {
-                    #(#yyrules)*
-                };
                 let yygoto = YY_RULE_INFO[yyruleno as usize] as i32;
-                let yyact = yy_find_reduce_action(yy, yygoto);
-                if yyact < YYNSTATE {
-                    yy_shift(yy, yyact, yygoto, yygotominor)?;
-                    Ok(())
-                } else {
-                    assert!(yyact == YYNSTATE + YYNRULE + 1);
-                    #accept_code
-                    Ok(())
+                let n = YY_RULE_NRHS[yyruleno as usize];
+                for (syms, prev) in yy_glr_paths(yy, &node, n, via.as_ref()) {
+                    let yyact = yy_find_reduce_action(prev.stateno, yygoto);
+                    if yyact == YYNSTATE + YYNRULE + 1 {
+                        /* The start symbol is accepted only at the end of the input */
+                        if yymajor == 0 {
+                            match &step.accepted {
+                                Some(sym) => yy_glr_add_alt(sym, yyruleno, syms),
+                                None => step.accepted = Some(yy_glr_sym(yygoto, YYGlrValue::Deferred(vec![(yyruleno, syms)]))),
+                            }
+                        }
+                        continue;
+                    }
+                    assert!(yyact < YYNSTATE);
+                    let next = step.frontier.iter().find(|next| next.stateno == yyact).cloned();
+                    let next = match next {
+                        Some(next) => next,
+                        None => {
+                            let sym = yy_glr_sym(yygoto, YYGlrValue::Deferred(vec![(yyruleno, syms)]));
+                            let next = yy_glr_node(yyact, vec![(prev, sym)]);
+                            step.frontier.push(next.clone());
+                            yy_glr_schedule(step, &next, yymajor);
+                            continue;
+                        }
+                    };
+                    let link = next.links.borrow().iter().find(|(p, _)| std::rc::Rc::ptr_eq(p, &prev)).map(|(_, sym)| sym.clone());
+                    match link {
+                        /* Another way to reduce the same symbol */
+                        Some(sym) => yy_glr_add_alt(&sym, yyruleno, syms),
+                        /* A new link into a node already reduced: reduce again the paths through it */
+                        None => {
+                            let sym = yy_glr_sym(yygoto, YYGlrValue::Deferred(vec![(yyruleno, syms)]));
+                            next.links.borrow_mut().push((prev, sym.clone()));
+                            for node in &step.frontier {
+                                for yyact in yy_find_all_actions(node.stateno, yymajor) {
+                                    if (YYNSTATE..YYNSTATE + YYNRULE).contains(&yyact) && YY_RULE_NRHS[(yyact - YYNSTATE) as usize] > 0 {
+                                        step.reduces.push((node.clone(), yyact - YYNSTATE, Some(sym.clone())));
+                                    }
+                                }
+                            }
+                        }
+                    }
                 }
             }
-        );
-        yyreduce_fn.to_tokens(&mut src);
 
-        Ok(src)
+            /*
+             ** Run the code of a rule once for each combination of the values of its symbols.
+             ** A value used by several combinations is cloned, except for the last one, that
+             ** takes it. If it cannot be cloned, only the last combination is run.
+             */
+            fn yy_glr_eval_alt #yy_generics_impl(yy: &mut Parser #yy_generics, yyruleno: i32,
+                                                children: &[std::rc::Rc<YYGlrSym #yy_generics>],
+                                                values: &mut std::vec::Vec<YYMinorType #yy_generics>,
+                                                error: &mut Option<#yyerrtype>)
+                #yy_generics_where
+            {
+                /* The values of each symbol, cloned if the symbol is used somewhere else too */
+                let mut args = std::vec::Vec::new();
+                for child in children {
+                    let arg: std::vec::Vec<_> = match yy_glr_eval(yy, child) {
+                        Err(e) => {
+                            error.get_or_insert(e);
+                            std::vec::Vec::new()
+                        }
+                        Ok(()) if std::rc::Rc::strong_count(child) > 1 => match &*child.value.borrow() {
+                            YYGlrValue::Values(values) => values.iter().map(yy_clone).collect(),
+                            YYGlrValue::Deferred(_) => std::vec::Vec::new(),
+                        },
+                        Ok(()) => yy_glr_take(child).into_iter().map(Some).collect(),
+                    };
+                    args.push(arg);
+                }
+
+                let total = args.iter().map(|arg| arg.len()).product::<usize>();
+                let mut uses = args.iter().map(|arg| vec![total / arg.len().max(1); arg.len()]).collect::<std::vec::Vec<_>>();
+                for k in 0..total {
+                    let mut rest = k;
+                    let mut entries = std::vec::Vec::new();
+                    for i in (0..args.len()).rev() {
+                        let j = rest % args[i].len();
+                        rest /= args[i].len();
+                        uses[i][j] -= 1;
+                        let minor = if uses[i][j] == 0 {
+                            args[i][j].take()
+                        } else {
+                            args[i][j].as_ref().and_then(yy_clone)
+                        };
+                        entries.push((children[i].major, minor));
+                    }
+                    entries.reverse();
+                    if entries.iter().all(|(_, minor)| minor.is_some()) {
+                        for (major, minor) in entries {
+                            yy.yystack.push(YYStackEntry { stateno: 0, major, minor: minor.unwrap() });
+                        }
+                        let yyactions: &[_] = &<Parser #yy_generics>::YY_ACTIONS;
+                        match yyactions[yyruleno as usize](yy) {
+                            Ok(value) => values.push(value),
+                            Err(e) => {
+                                error.get_or_insert(e);
+                            }
+                        }
+                    } else {
+                        for (major, minor) in entries {
+                            if let Some(minor) = minor {
+                                yy_destructor(yy, major, minor);
+                            }
+                        }
+                    }
+                }
+                /* The values left, if some combination could not be run */
+                for (child, arg) in children.iter().zip(args) {
+                    for minor in arg.into_iter().flatten() {
+                        yy_destructor(yy, child.major, minor);
+                    }
+                }
+            }
+
+            /*
+             ** Run the deferred code of a symbol, for each way it was reduced, and merge the values
+             ** with %merge. A way that fails is dropped, and the error is returned only if every
+             ** way fails.
+             */
+            fn yy_glr_eval #yy_generics_impl(yy: &mut Parser #yy_generics, sym: &YYGlrSym #yy_generics) -> ::core::result::Result<(), #yyerrtype>
+                #yy_generics_where
+            {
+                let alts = match &mut *sym.value.borrow_mut() {
+                    YYGlrValue::Values(_) => return Ok(()),
+                    YYGlrValue::Deferred(alts) => ::core::mem::take(alts),
+                };
+                let mut values = std::vec::Vec::new();
+                let mut error = None;
+                for (yyruleno, children) in alts {
+                    yy_glr_eval_alt(yy, yyruleno, &children, &mut values, &mut error);
+                    yy_glr_release(yy, std::vec::Vec::new(), children);
+                }
+
+                let mut merged = std::vec::Vec::new();
+                for value in values {
+                    let value = match merged.pop() {
+                        None => value,
+                        Some(prev) => match yy_merge(yy, sym.major, prev, value) {
+                            Ok(value) => value,
+                            Err((prev, value)) => {
+                                merged.push(prev);
+                                value
+                            }
+                        },
+                    };
+                    merged.push(value);
+                }
+                let failed = merged.is_empty();
+                *sym.value.borrow_mut() = YYGlrValue::Values(merged);
+                match error {
+                    Some(e) if failed => Err(e),
+                    _ => Ok(()),
+                }
+            }
+
+            /*
+             ** Push the symbols of a path to the LR stack, running their deferred code.
+             */
+            fn yy_glr_push #yy_generics_impl(yy: &mut Parser #yy_generics, path: std::vec::Vec<(i32, std::rc::Rc<YYGlrSym #yy_generics>)>)
+                -> ::core::result::Result<(), #yyerrtype>
+                #yy_generics_where
+            {
+                let mut res = Ok(());
+                for (stateno, sym) in path {
+                    if res.is_ok() {
+                        res = yy_glr_eval(yy, &sym);
+                    }
+                    if res.is_ok() {
+                        /* If it is still ambiguous, the first value is taken */
+                        let mut values = yy_glr_take(&sym).into_iter();
+                        let value = values.next().unwrap_or_else(|| unreachable!("symbol without a value"));
+                        for other in values {
+                            yy_destructor(yy, sym.major, other);
+                        }
+                        res = yy_shift(yy, stateno, sym.major, value);
+                    }
+                    yy_glr_release(yy, std::vec::Vec::new(), vec![sym]);
+                }
+                res
+            }
+
+            /*
+             ** When a single branch is left, run the code deferred while the parser was split and
+             ** go back to the LR stack. Unless a symbol is still ambiguous, without a %merge: then
+             ** its values are kept in the graph, until the end of the input.
+             */
+            fn yy_glr_join #yy_generics_impl(yy: &mut Parser #yy_generics) -> ::core::result::Result<(), #yyerrtype>
+                #yy_generics_where
+            {
+                if yy.yyglr.len() != 1 {
+                    return Ok(());
+                }
+                let path = match yy_glr_path(yy, &yy.yyglr[0], true) {
+                    Some(path) => path,
+                    None => return Ok(()),
+                };
+                for (_, sym) in &path {
+                    yy_glr_eval(yy, sym)?;
+                }
+                let single = path.iter().all(|(_, sym)| {
+                    matches!(&*sym.value.borrow(), YYGlrValue::Values(values) if values.len() == 1)
+                });
+                if !single {
+                    return Ok(());
+                }
+                yy_glr_clear(yy);
+                yy_glr_push(yy, path)
+            }
+
+            fn yy_glr_parse_token #yy_generics_impl(yy: &mut Parser #yy_generics,
+                                                        yymajor: i32, yyminor: YYMinorType #yy_generics) -> ::core::result::Result<(), #yyerrtype>
+                #yy_generics_where
+            {
+                if yy.yyglr.is_empty() {
+                    /* Parse as a LR parser, until a conflict splits the stack */
+                    loop {
+                        let stateno = yy.yystack.last().unwrap().stateno;
+                        if yy_find_glr_actions(stateno, yymajor).next().is_some() {
+                            let base = yy_glr_node(stateno, std::vec::Vec::new());
+                            yy.yyglr.push(base.clone());
+                            yy.yyglrbase = Some(base);
+                            break;
+                        }
+                        let yyact = yy_find_shift_action(stateno, yymajor);
+                        if (YYNSTATE..YYNSTATE + YYNRULE).contains(&yyact) {
+                            yy_reduce(yy, yyact - YYNSTATE)?;
+                            if !yy.yystatus.is_normal() {
+                                return Ok(());
+                            }
+                        } else {
+                            /* Shift the token, or recover from the error */
+                            return yy_parse_token_2(yy, yymajor, yyminor);
+                        }
+                    }
+                }
+
+                /* Reduce every branch until it is ready to shift the token */
+                let mut step = YYGlrStep {
+                    frontier: yy.yyglr.clone(),
+                    reduces: std::vec::Vec::new(),
+                    shifts: std::vec::Vec::new(),
+                    accepted: None,
+                };
+                for node in yy.yyglr.clone() {
+                    yy_glr_schedule(&mut step, &node, yymajor);
+                }
+                while let Some((node, yyruleno, via)) = step.reduces.pop() {
+                    yy_glr_reduce(yy, &mut step, yymajor, node, yyruleno, via);
+                }
+                let YYGlrStep { frontier, shifts, accepted, .. } = step;
+
+                if let Some(root) = accepted {
+                    /* The graph is dropped first, so that the values are not shared anymore */
+                    yy_glr_release(yy, frontier, std::vec::Vec::new());
+                    yy_glr_clear(yy);
+                    yy_glr_eval(yy, &root)?;
+                    yy.yystatus = YYStatus::Accepted(yy_glr_take(&root));
+                    yy.yystack.clear();
+                    return Ok(());
+                }
+
+                if shifts.is_empty() {
+                    /* Every branch failed: recover from the error with the first one */
+                    let path = yy_glr_path(yy, &yy.yyglr[0], false).unwrap();
+                    yy_glr_release(yy, frontier, std::vec::Vec::new());
+                    yy_glr_clear(yy);
+                    yy_glr_push(yy, path)?;
+                    return yy_parse_token_2(yy, yymajor, yyminor);
+                }
+
+                /* Shift the token into every branch that can, the nodes with the same state are merged */
+                let sym = yy_glr_sym(yymajor, YYGlrValue::Values(vec![yyminor]));
+                let mut tops: std::vec::Vec<std::rc::Rc<YYGlrNode #yy_generics>> = std::vec::Vec::new();
+                for (node, yyact) in shifts {
+                    match tops.iter().find(|top| top.stateno == yyact) {
+                        Some(top) => top.links.borrow_mut().push((node, sym.clone())),
+                        None => tops.push(yy_glr_node(yyact, vec![(node, sym.clone())])),
+                    }
+                }
+                let mut nodes = ::core::mem::replace(&mut yy.yyglr, tops);
+                nodes.extend(frontier);
+                yy_glr_release(yy, nodes, vec![sym]);
+                yy.error_count = yy.error_count.saturating_sub(1);
+                yy_glr_join(yy)
+            }
+        });
+        Ok(())
     }
 
     fn translate_code(&self, rp: &Rule) -> syn::Result<TokenStream> {
@@ -2835,7 +6098,8 @@ impl Pomelo {
             if let (Some(alias), MultiTerminal(ss)) = (alias, &r.typ) {
                 for or in &ss[1..] {
                     if r.dt_num != self.the_symbols.get(or).dt_num {
-                        return error_span(
+                        return error_at(
+                            &rp.location,
                             alias.span(),
                             "Compound tokens with an alias must all have the same type",
                         ); //tested
@@ -2853,7 +6117,11 @@ impl Pomelo {
             match alias {
                 Some(alias) => {
                     if Some(r) == self.wildcard.as_ref() {
-                        return error_span(alias.span(), "Wildcard token must not have an alias");
+                        return error_at(
+                            &rp.location,
+                            alias.span(),
+                            "Wildcard token must not have an alias",
+                        );
                         //tested
                     }
                     yypattern.push(quote!(YYMinorType::#yydt(#alias)))
@@ -2875,3 +6143,78 @@ impl Pomelo {
         Ok(code)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Builds the grammar with the given %algorithm, and returns the number of states
+    fn num_states(grammar: &str, algorithm: &str) -> syn::Result<usize> {
+        let grammar = format!("%algorithm {}; {}", algorithm, grammar);
+        let crate::Decls(decls, _) = syn::parse_str(&grammar)?;
+        let mut pomelo = Pomelo::new_from_decls(decls)?;
+        pomelo.build()?;
+        Ok(pomelo.states.len())
+    }
+
+    //A LALR(1) grammar: the canonical LR(1) states are split by their look-ahead, the minimal
+    //ones are merged back as in LALR(1)
+    #[test]
+    fn algorithm_states_lalr() {
+        let grammar = r#"
+            %left Plus;
+            %left Times;
+            input ::= expr;
+            expr ::= expr Plus expr;
+            expr ::= expr Times expr;
+            expr ::= LParen expr RParen;
+            expr ::= Num;
+        "#;
+        let lalr = num_states(grammar, "lalr").unwrap();
+        let minimal = num_states(grammar, "minimal").unwrap();
+        let lr1 = num_states(grammar, "lr1").unwrap();
+        assert!(lalr <= minimal, "{} <= {}", lalr, minimal);
+        assert!(minimal < lr1, "{} < {}", minimal, lr1);
+    }
+
+    //A LR(1) grammar that is not LALR(1): the states that would conflict are not merged
+    #[test]
+    fn algorithm_states_lr1() {
+        let grammar = r#"
+            input ::= A e C;
+            input ::= A f D;
+            input ::= B f C;
+            input ::= B e D;
+            e ::= E;
+            f ::= E;
+        "#;
+        assert!(num_states(grammar, "lalr").is_err());
+        let minimal = num_states(grammar, "minimal").unwrap();
+        let lr1 = num_states(grammar, "lr1").unwrap();
+        assert!(minimal <= lr1, "{} <= {}", minimal, lr1);
+    }
+
+    //The first and follow sets of a grammar with more than 64 terminals take several words, the
+    //parser must be the same as the one built with a BTreeSet
+    #[test]
+    fn bitset_expansion() {
+        let mut grammar = String::from("input ::= stmt*; x ::= Num; y ::= Num;");
+        //The state after `Num` reduces `x` or `y` depending on the look-ahead
+        for i in 0..40 {
+            grammar += &format!("stmt ::= Begin{0} x End{0};", i);
+            grammar += &format!("stmt ::= Begin{0} y Stop{0};", i);
+        }
+        for algorithm in &["lalr", "minimal"] {
+            let grammar = format!("%algorithm {}; {}", algorithm, grammar);
+            let expand = |btree| {
+                bitset::use_btree_set(btree);
+                let crate::Decls(decls, _) = syn::parse_str(&grammar).unwrap();
+                let mut pomelo = Pomelo::new_from_decls(decls).unwrap();
+                let expanded = pomelo.build().unwrap().to_string();
+                bitset::use_btree_set(false);
+                expanded
+            };
+            assert_eq!(expand(false), expand(true));
+        }
+    }
+}
@@ -158,7 +158,7 @@ The most obvious difference here is that *lemon* is written in C and generates C
 *pomelo* is written in Rust and produces Rust code. Many other differences arise from this fact:
 
  * Since there is no command to call, there are no command line switches.
- * No `%destructor` or `%default_destructor` or `%token_destructor` directives. Rust `drop` semantics should take care or everything.
+ * No `%default_destructor` directive. Rust `drop` semantics take care of releasing the values, so `%destructor` and `%token_destructor` are only needed to do something else, such as updating the `%extra_argument`.
  * No `%parse_accept` directive. If you want to run code after the end-of-input, just do it after calling Parser::end_of_input().
  * No `%token_type` directive. See below for details.
  * New `%extra_token` directive.
@@ -229,6 +229,65 @@ optional_list ::= list(D) { Some(D) }
 optional_list ::= { None }
 ```
 
+Similarly, you can add `*` or `+` at the end of a symbol to repeat it zero or more times, or one or
+more times, respectively. The type of the alias is then changed from `T` to `Vec<T>`. For example:
+
+```text
+%type arg T;
+
+call ::= Ident LParen arg*(A) RParen { ... }
+```
+
+is actually converted to something like:
+
+```text
+%type arg T;
+%type arg_list Vec<T>;
+
+call ::= Ident LParen arg_list(A) RParen { ... }
+arg_list ::= { Vec::new() }
+arg_list ::= arg_list(mut L) arg(D) { L.push(D); L }
+```
+
+The generated rules are left-recursive, so they do not grow the parser stack. With `+` the first
+rule is `arg_list ::= arg(D) { vec![D] }` instead, so that an empty list is a syntax error.
+
+Lists with a separator are written adding `%` and the separator token after the symbol. The value
+is again a `Vec<T>` with the items, and the separators are discarded. By default the list has one
+or more items, but you can use `*` to allow an empty list, and `?` after the separator to allow a
+trailing separator:
+
+```text
+%type Ident String;
+
+args ::= Ident % Comma(A) { ... }       // a, a, a
+args ::= Ident* % Comma(A) { ... }      // empty, or a, a, a
+args ::= Ident* % Comma?(A) { ... }     // also a, a, a,
+```
+
+The separator must be a token, or several tokens separated with `|`.
+
+Several symbols can be grouped with parentheses, usually to apply one of the modifiers above to
+all of them. Inside a group `|` separates alternatives:
+
+```text
+%type expr T;
+
+list ::= expr(A) (Comma expr)*(R) { ... }   // R: Vec<T>
+item ::= Let (Ident Eq expr)?(V) { ... }    // V: Option<(String, T)>
+stmt ::= (decl | expr Semicolon)(S) { ... }
+```
+
+The value of a group is the value of the symbols with a type: `()` if there are none, the value
+itself if there is only one, or a tuple if there are more. If every alternative has the same type
+that is the type of the group. If not, the value is wrapped in a generated enum `Alt2<A, B>`,
+`Alt3<A, B, C>`... with a variant for each alternative, named `A`, `B`, `C`... in order. The
+symbols inside a group cannot have aliases. A group of single tokens of the same type, such as
+`(Plus | Minus)`, is just the same as the compound token `Plus|Minus`.
+
+The hidden non-terminals created by these constructs are shown in the `%verbose` report with
+names such as `list?`, `Ident+%Comma`, `Ident*%Comma?` or `(Comma expr)*`.
+
 
 ## Macro input
 
@@ -245,6 +304,7 @@ conventions as Rust.
 A terminal symbol (token) is any string of alphanumeric and underscore characters that begins with an upper case letter. A terminal can contain lowercase letters after the first character. A nonterminal, on the other hand, is any string of alphanumeric and underscore characters that begins with a lower case letter.
 
 In *pomelo*, terminal and nonterminal symbols do not need to be declared or identified in a separate section of the grammar. *Pomelo* is able to generate a list of all terminals and nonterminals by examining the grammar rules, and it can always distinguish a terminal from a nonterminal by checking the case of the first character of the name.
+Symbols can also be declared explicitly, and then they can have any name, see [`%strict`](#the-strict-terminal-and-nonterminal-directives).
 
 *Yacc* and *bison* allow terminal symbols to have either alphanumeric names or to be individual characters included in single quotes, like this: `)` or `$`. *Pomelo* does not allow this alternative form for terminal symbols. With *pomelo*, all symbols, terminals and nonterminals, must have alphanumeric names.
 
@@ -332,6 +392,46 @@ If you use a symbolic name (`(B)` in the example) with such a compound token, th
 must be of the same type. However, if there is no symbolic name, then they may have different
 types.
 
+Non-terminals can also have parameters, written in parentheses after the name of the left-hand
+side. They work like a macro: every time the non-terminal is used with a distinct list of arguments,
+a new non-terminal is created from these rules, replacing the parameters with the arguments.
+
+```text
+%type list(X) Vec<X>;
+list(X) ::= X(A) { vec![A] }
+list(X) ::= list(X)(mut L) Comma X(A) { L.push(A); L }
+
+%type pair(K, V) (K, V);
+pair(K, V) ::= K(k) Colon V(v) { (k, v) }
+
+object ::= LBrace list(pair(Ident, value))(L) RBrace { ... }
+args ::= LParen list(expr)(L) RParen { ... }
+```
+
+The arguments can be any symbol, including groups and other parameterized non-terminals. An
+argument with several symbols is a group, so `list(Comma expr)` is the same as
+`list((Comma expr))`. As shown above, the `%type` of a parameterized non-terminal can use its
+parameters too, they are replaced with the types of the arguments. The created non-terminals are
+shown in the `%verbose` report with names such as `list(expr)`.
+
+A block of code can also be written between the symbols of the right-hand side. This is a mid-rule
+action, and it is run as soon as the symbols before it are parsed, before the rest of the rule:
+
+```text
+block ::= LBrace { extra.open_scope() }(scope: ScopeId) stmt*(S) RBrace {
+    extra.close_scope(scope);
+    Block::new(S)
+}
+```
+
+Each mid-rule action is a new empty non-terminal, named `@1`, `@2` and so on in the `%verbose`
+report, whose only rule runs that code. Its value can be bound to a pattern and used in the action
+of the rule, but since it has no `%type` declaration the type is written with the pattern:
+`{ ... }(pattern: Type)`. Without a type, the code must evaluate to `()`, even if there is a
+`%default_type`. The code has `extra` available, but not the values of the previous symbols. Also
+note that this new non-terminal must be reduced before the parser knows which rule it is in, so a
+mid-rule action may cause conflicts that the same rule without it does not have.
+
 ### Precedence Rules
 
 *pomelo* resolves parsing ambiguities in exactly the same way as *yacc* and *bison*. A shift-reduce
@@ -410,6 +510,21 @@ expr ::= Minus expr [Not];
 
 This rule has a precedence equal to that of the Not symbol, not the Minus symbol as would have been the case by default.
 
+The symbol in square braces does not need to be used anywhere else. A token that is only used to
+give precedence to rules, such as `UMinus` below, is never in the input, so it is not added to the
+`Token` enum:
+
+```text
+%left Plus Minus;
+%left Times;
+%precedence UMinus;
+expr ::= Minus expr [UMinus];
+```
+
+The `%precedence` directive declares a precedence level, like `%left` or `%right`, but without
+associativity. It is for symbols where the associativity should never be needed: if it is, the
+conflict is not resolved by precedence and it is reported.
+
 With the knowledge of how precedence is assigned to terminal symbols and individual grammar rules,
 we can now explain precisely how parsing conflicts are resolved in *pomelo*. Shift-reduce
 conflicts are resolved as follows:
@@ -427,6 +542,22 @@ Reduce-reduce conflicts are resolved this way:
  * If both rules have precedence and the precedence is different then resolve the dispute in favor of the rule with the highest precedence and do not report a conflict.
  * Otherwise, resolve the conflict by reducing by the rule that appears first in the grammar and report a parsing conflict.
 
+Any reported parsing conflict is a compiler error, unless they are expected with the `%expect`
+and `%expect_rr` directives. There is an error for each conflict, that points to the rules in
+conflict and to the look-ahead token. It is followed by an example of the input before and after
+the conflict, marked with `*`, and how each of the conflicting actions would derive it, each one
+as an error of its own. For example, the famous dangling `else`:
+
+```text
+Shift/reduce conflict in state 5 on Else, shift with `stmt ::= If Cond Then stmt Else stmt` or reduce with `stmt ::= If Cond Then stmt`
+Shift example: If Cond Then If Cond Then stmt * Else stmt
+Shift derivation: If Cond Then [stmt ::= If Cond Then stmt * Else stmt]
+Reduce example: If Cond Then If Cond Then stmt * Else stmt
+Reduce derivation: [stmt ::= If Cond Then [stmt ::= If Cond Then stmt *] Else stmt]
+```
+
+The full report of the states is printed with the `%verbose` directive.
+
 ### Special Directives
 
 The input grammar to *pomelo* consists of grammar rules and special directives. We've described all
@@ -442,17 +573,23 @@ directives is arbitrary.
  * [`%module`](#the-module-directive)
  * [`%type`](#the-type-directive)
  * [`%include`](#the-include-directive)
+ * [`%include_grammar`](#the-include_grammar-directive)
+ * [`%include_lemon`](#the-include_lemon-directive)
  * [`%syntax_error`](#the-syntax_error-directive)
  * [`%parse_fail`](#the-parse_fail-directive)
  * [`%stack_overflow`](#the-stack_overflow-directive)
+ * [`%destructor`](#the-destructor-and-token_destructor-directives)
+ * [`%token_destructor`](#the-destructor-and-token_destructor-directives)
  * [`%stack_size`](#the-stack_size-directive)
  * [`%left`](#the-left-right-nonassoc-directives)
  * [`%right`](#the-left-right-nonassoc-directives)
  * [`%nonassoc`](#the-left-right-nonassoc-directives)
+ * [`%precedence`](#the-left-right-nonassoc-directives)
  * [`%default_type`](#the-default_type-directive)
  * [`%extra_argument`](#the-extra_argument-directive)
  * [`%error`](#the-error-directive)
  * [`%start_symbol`](#the-start_symbol-directive)
+ * [`%inline`](#the-inline-directive)
  * [`%fallback`](#the-fallback-directive)
  * [`%wildcard`](#the-wildcard-directive)
  * [`%token_class`](#the-token_class-directive)
@@ -460,6 +597,17 @@ directives is arbitrary.
  * [`%parser`](#the-parser-directive)
  * [`%extra_token`](#the-extra_token-directive)
  * [`%verbose`](#the-verbose-directive)
+ * [`%expect`](#the-expect-and-expect_rr-directives)
+ * [`%expect_rr`](#the-expect-and-expect_rr-directives)
+ * [`%algorithm`](#the-algorithm-directive)
+ * [`%glr`](#the-glr-and-merge-directives)
+ * [`%merge`](#the-glr-and-merge-directives)
+ * [`%deny`](#the-deny-and-allow-directives)
+ * [`%allow`](#the-deny-and-allow-directives)
+ * [`%strict`](#the-strict-terminal-and-nonterminal-directives)
+ * [`%terminal`](#the-strict-terminal-and-nonterminal-directives)
+ * [`%nonterminal`](#the-strict-terminal-and-nonterminal-directives)
+ * [`%tables`](#the-tables-directive)
 
 #### The `%module` directive
 
@@ -530,6 +678,68 @@ The `%include` directive is very handy for using symbols declared elsewhere. For
 %include { use super::*; }
 ```
 
+#### The `%include_grammar` directive
+
+The `%include_grammar` directive reads more declarations from a file, as if they were written in
+place of the directive. The path is relative to the directory of the crate being compiled, that is
+`CARGO_MANIFEST_DIR`. This is useful for big grammars:
+
+```text
+pomelo! {
+    %include_grammar "grammar/sql.pomelo";
+}
+```
+
+The file can have any declaration, including other `%include_grammar` directives. Since the
+tokens read from a file cannot point into it, the errors found there are reported at the
+directive, and their message starts with the path of the file.
+
+#### The `%include_lemon` directive
+
+The `%include_lemon` directive reads a grammar file written for *lemon*, so that it can be reused
+without rewriting it. Optionally, it takes a second file, in *pomelo* syntax, with the Rust code of
+the actions:
+
+```text
+pomelo! {
+    %include_lemon "grammar/parse.y" "grammar/parse_actions.pomelo";
+}
+```
+
+Both paths are relative to the directory of the crate, as in `%include_grammar`. Only the grammar
+itself is imported: the rules, the `%left`, `%right`, `%nonassoc`, `%token`, `%fallback`,
+`%wildcard`, `%token_class`, `%start_symbol` and `%stack_size` directives. The directives that
+contain C code or C types, such as `%include`, `%type`, `%token_type` or `%syntax_error`, are an
+error, as are `%name` and `%token_prefix`, and all of them are reported at once. The Rust versions
+of those can be written in the file of actions or in the macro. Conditional blocks with `%ifdef`,
+`%ifndef`, `%else` and `%endif` are supported, with only the symbol `POMELO` defined, so the C
+parts of a grammar can be kept for *lemon* inside `%ifndef POMELO` blocks. Other unknown
+directives are an error.
+
+A rule in the file of actions replaces the rule of the *lemon* grammar with the same symbols,
+aliases apart. It is an error if there is no such rule. The other declarations in that file are
+just added to the grammar. For example, for these *lemon* rules:
+
+```text
+%left PLUS.
+expr(A) ::= expr(B) PLUS expr(C). { A = B + C; }
+expr(A) ::= INTEGER(B). { A = B; }
+```
+
+the file of actions could be:
+
+```text
+%type expr i64;
+%type INTEGER i64;
+expr ::= expr(B) PLUS expr(C) { B + C }
+expr ::= INTEGER;
+```
+
+The *lemon* rules that have C code but no Rust action are left as stubs that panic when reduced,
+with the location of the rule in the message. Since *lemon* token classes are lowercase, they are
+renamed with the first letter in uppercase: `%token_class number INT|FLOAT.` creates the token
+class `Number`.
+
 #### The `%syntax_error` directive
 
 The `%syntax_error` directive specify code that will be called when a syntax error occurs. This code must evaluate to a value of type `Result<(), Error>`, and it is run in an function that returns the same type so you can also use the `?` operator. If it evaluates to `Ok(())`, the parser will try to recover and continue. If it evaluates to `Err(_)` or a `?` fails, the parser will fail with that error value. See the section [Error Processing](#error-processing) for more details.
@@ -582,6 +792,40 @@ After a stack overflow this parser object must not be used again.
 
 See also the `%stack_size` directive for more details about the parser stack.
 
+#### The `%destructor` and `%token_destructor` directives
+
+The values of the symbols in the parser stack are usually consumed by the reduction of a rule. But
+some of them are discarded without reaching any action: when the error recovery pops the stack,
+when a token is thrown away after a syntax error, or when the parser fails. Those values are
+dropped, as usual in Rust, but sometimes that is not enough. For example if the values are indices
+into an arena stored in the `%extra_argument`.
+
+The `%destructor` directive specifies a block of Rust code that is run when a value of the given
+symbol is discarded. As in a rule, the value can be bound to a pattern, and `extra` is available
+as a mutable reference to the current `extra_argument`:
+
+```text
+%type expr NodeId;
+%destructor expr(id) {
+    extra.free_node(id);
+}
+```
+
+The `%token_destructor` directive is the same, but it is used for all the tokens that have no
+`%destructor`. Since each token can have a different type, in this code the discarded value is
+available as `token`, of type `Token`:
+
+```text
+%token_destructor {
+    extra.log.push(format!("discarded {:?}", token));
+}
+```
+
+Destructors are run from the top of the stack to the bottom, and always before the `%parse_fail`
+or the `%stack_overflow` code. Note that if the parser object is dropped before finishing, either
+by `end_of_input()` or by an error, the values in the stack are just dropped, with no call to the
+destructors.
+
 #### The `%stack_size` directive
 
 If stack overflow is a problem and you can't resolve the trouble by using left-recursion, then you
@@ -597,6 +841,19 @@ If you specify the limit as `0` then it means _unlimited_. Beware that if your p
 untrusted, having an unlimited stack may be an issue (it may eat up all your memory). If you do
 this, the code in `%stack_overflow` directive is never called.
 
+*Pomelo* computes the maximum depth that the stack can reach with any input, and the generated
+module has it as a constant `MAX_STACK_DEPTH: Option<usize>`. It is `None` if there is no such
+maximum, because some rule can nest without limit, and the `%verbose` directive lists those rules.
+That happens with right recursion, such as `list ::= Item list`, and with self-embedding, such as
+`expr ::= LParen expr RParen`, but not with left recursion, such as `list ::= list Item`. Note
+that `expr ::= expr Plus expr` is also unbounded, even if precedence rules make it left
+associative. If you specify the limit as `auto`, then it is that maximum depth, and if there is
+none it is an error:
+
+```text
+%stack_size auto;
+```
+
 This directive has an additional optional parameter that is the type to implement the stack. It is
 by default `std::vec::Vec`, but you may specify whatever type you want, as long as it complies the
 following interface:
@@ -622,9 +879,9 @@ You can use alternative types for the stack to make your parser `no-std` complia
 
 #### The `%left`, `%right`, `%nonassoc` directives
 
-The `%left`, `%right` and `%nonassoc` directives are used to declare precedences of terminal
-symbols. Every terminal symbol whose name appears in one of those directives is given the same
-associative precedence value. Subsequent directives have higher precedence. For example:
+The `%left`, `%right`, `%nonassoc` and `%precedence` directives are used to declare precedences
+of terminal symbols. Every terminal symbol whose name appears in one of those directives is given
+the same associative precedence value. Subsequent directives have higher precedence. For example:
 
 ```text
 %left And;
@@ -637,6 +894,9 @@ associative precedence value. Subsequent directives have higher precedence. For
 
 Note the semi-colon that terminates each `%left`, `%right` or `%nonassoc` directive.
 
+The `%precedence` directive gives precedence but no associativity. See
+[Precedence Rules](#precedence-rules).
+
 LALR(1) grammars can get into a situation where they require a large amount of stack space if you
 make heavy use or right-associative operators. For this reason, it is recommended that you use
 `%left` rather than `%right` whenever possible.
@@ -692,6 +952,43 @@ directive.
 %start_symbol program;
 ```
 
+You can also list additional start symbols, to parse just a fragment of the grammar:
+
+```text
+%start_symbol program expr type_expr;
+```
+
+The first one is the main start symbol, used by `Parser::new()`. Each of the others gets its own
+constructor named after the symbol, such as `Parser::new_expr()` and `Parser::new_type_expr()`.
+They return an `ExprParser` and a `TypeExprParser` respectively, with the same `parse()`
+function, but whose `end_of_input()` returns the type of that symbol. Unlike the main start
+symbol, the additional ones can be used in the right-hand side of other rules.
+
+#### The `%inline` directive
+
+Helper non-terminals, such as a `binop` that matches any binary operator, hide the precedence of
+the tokens they contain: the rule that uses them has no precedence of its own, so they usually end
+up in conflicts. The `%inline` directive declares non-terminals that are substituted into every rule
+that uses them, as if each of its productions were written there by hand:
+
+```text
+%inline binop;
+%left Plus Minus;
+%left Times;
+
+expr ::= expr(a) binop(op) expr(b) { op(a, b) }
+binop ::= Plus { |a, b| a + b }
+binop ::= Minus { |a, b| a - b }
+binop ::= Times { |a, b| a * b }
+```
+
+This grammar has the three rules `expr ::= expr Plus expr`, `expr ::= expr Minus expr` and
+`expr ::= expr Times expr`, each with the precedence of its operator. The value of `op` is computed
+by the code of the corresponding `binop` rule. The aliases of the inline rules are only visible in
+their own code, so they do not clash with those of the outer rule.
+
+An inline non-terminal cannot be recursive, nor be a start symbol.
+
 #### The `%fallback` directive
 
 This directive defines an alternative token that will be used instead of another if the original one cannot be parsed. For example:
@@ -787,6 +1084,157 @@ These are particularly useful in the `%syntax_error` code to build a meaninful e
 #### The `%verbose` directive
 
 This directive makes *pomelo* to dump the built states of the grammar to the console. This is mostly useful for diagnostics or for fine tuning your grammar.
+It also shows the maximum depth of the parser stack, or the rules that make it unbounded, see [`%stack_size`](#the-stack_size-directive).
+
+#### The `%expect` and `%expect_rr` directives
+
+Sometimes a grammar has a parsing conflict that is well understood, and that is resolved just fine
+by the default rules, such as the famous dangling `else`. The `%expect` directive specifies the
+number of shift-reduce conflicts that the grammar is expected to have, and `%expect_rr` the
+number of reduce-reduce conflicts:
+
+```text
+%expect 1;
+stmt ::= If cond Then stmt;
+stmt ::= If cond Then stmt Else stmt;
+```
+
+If there are exactly that many conflicts the parser is built as usual, but if the number is
+different, either more or less, it is a compiler error. So a new conflict will not go unnoticed,
+even if it happens to replace an old one. Without these directives, the expected number is `0`.
+
+#### The `%algorithm` directive
+
+By default, *pomelo* builds LALR(1) tables, just like Lemon. LALR(1) merges the states that have the
+same rules and parse points, even if they come from different contexts. This keeps the tables small,
+but sometimes it creates reduce-reduce conflicts that are not real ambiguities of the grammar. This
+directive chooses a different construction:
+
+```text
+%algorithm minimal;
+```
+
+The possible values are:
+ * `lalr`: the default LALR(1) tables.
+ * `lr1`: canonical LR(1) tables. The states are never merged, so they are free of those
+   conflicts, but there are many more states.
+ * `minimal`: minimal LR(1) tables, in the style of Pager's algorithm. The canonical LR(1) states
+   are built first, and those with the same rules and parse points are merged as in LALR(1),
+   except when doing so would create a conflict that none of the original states had. If the
+   grammar is LALR(1), the tables are the same as with `lalr`. Since it starts from the canonical
+   LR(1) states, building the tables takes as long as with `lr1`.
+
+Either way, the generated parser works the same, only the tables are different.
+
+#### The `%glr` and `%merge` directives
+
+Some grammars are ambiguous by nature, or they need an unbounded look-ahead. With this directive
+the parsing conflicts are not an error anymore, and the generated parser is a GLR parser:
+
+```text
+%glr;
+```
+
+While there is no conflict, it works just like the LR parser. When it reaches a conflict, the
+stack is split, and every action is tried. The branches are kept in a graph-structured stack:
+they share the part of the stack below the point where they split, and two branches that reach
+the same state after the same token are joined into one. So an ambiguous input, such as
+`1 + 2 + 3 + ...` with an ambiguous `expr ::= expr Plus expr`, needs a graph that grows
+polynomially with the length of the input, instead of exponentially.
+
+A branch that cannot parse the next token just dies. If no branch is left after a syntax error,
+the error is processed as usual, using the first branch that failed.
+
+The code of the rules is not run while the parser is split. Each non-terminal keeps every way it
+was reduced, and when a single branch is left, the code is run for the way or ways that are left.
+So the code of a branch that dies is never run, and the code of the rules can modify `extra`. If
+the deferred code of a rule returns an error, that way is dropped, unless it is the only one, and
+then the parse fails with that error.
+
+When there is more than one way to reduce a non-terminal, if there is a `%merge` for it, the values
+are merged into one. The `%merge` has two patterns for the values to merge and the code to build
+the merged value:
+
+```text
+%type expr Expr;
+%merge expr(a, b) { Expr::Ambiguous(Box::new(a), Box::new(b)) }
+```
+
+The code can use the `extra` argument, just like the code of a rule. Without a `%merge`, all the
+values are kept, and in the end, `end_of_input()` returns a `Vec` with the value of every way the
+input could be parsed. Then a value that is used by more than one of them is cloned, if its type
+implements `Clone`. If it does not, only the last one that uses it gets the value, and the others
+are dropped.
+
+The conflicts are still counted, so if `%expect` or `%expect_rr` are used, the numbers must be
+right. The graph is built with `Rc`, so a GLR parser does not implement `Send`.
+
+#### The `%deny` and `%allow` directives
+
+Before building the parser, *pomelo* looks for some common mistakes in the grammar, and reports
+each one as a compiler error. The checks are:
+ * `unreachable`: a non-terminal that cannot be reached from the start symbol.
+ * `non_productive`: a non-terminal that never derives a sequence of tokens, because every one of
+   its rules recurses forever.
+ * `unused_token`: a token declared with a type or with a precedence, that is not used in any
+   rule. Tokens with attributes, tokens declared without a type, such as `%type Space;` or a Lemon
+   `%token`, tokens in a `%token_class`, `%fallback` tokens and the `%wildcard` are not reported,
+   because they are used in other ways.
+ * `duplicate_rule`: the same rule written twice.
+ * `typo`: a token used only once, that is not declared anywhere, and that is just one letter away
+   from another token. A misspelled token is just a new token, so this catches errors that would go
+   unnoticed otherwise. Names shorter than 4 letters are not checked.
+
+The `non_productive`, `duplicate_rule` and `typo` checks are enabled by default. The
+`unreachable` and `unused_token` checks are disabled by default, because a valid grammar, such as
+one that is still being written, often triggers them.
+
+The `%deny` directive enables the given checks, and `%allow` disables them:
+
+```text
+%deny unreachable unused_token;
+%allow typo;
+```
+
+#### The `%strict`, `%terminal` and `%nonterminal` directives
+
+The `%terminal` and `%nonterminal` directives declare the kind of some symbols:
+
+```text
+%terminal num plus;
+%nonterminal Expr;
+```
+
+A declared symbol does not need to follow the uppercase/lowercase convention, so with this example
+`num` is a token, the `Token` enum has a `Token::num` variant, and `Expr` is a non-terminal. The
+declarations can be anywhere in the grammar, even after the symbols are used. A token declared this
+way but not used in any rule is reported by the [`unused_token`](#the-allow-directive) lint.
+
+Undeclared symbols are still told apart by the case of their first letter, unless the `%strict;`
+directive is used. Then every symbol must be declared, and using any other symbol is an error. This
+is a good way to catch misspelled symbols in big grammars, that would be silently taken as new
+symbols otherwise. The `error` symbol is predefined, so it does not need a declaration.
+
+A separator (`expr+ % Comma`) must still start with an uppercase letter, because that is how it is
+told apart from a directive.
+
+#### The `%tables` directive
+
+By default the action tables are compressed as in Lemon: the actions of all the states are
+overlapped in a single array, so finding an action needs a few checks, and maybe a second search
+for a fallback token or the wildcard. This directive chooses the format of the tables:
+
+```text
+%tables dense;
+```
+
+The possible values are:
+ * `compressed`: the default Lemon tables.
+ * `dense`: a full table with an action for every state and symbol. The fallback tokens and the
+   wildcard are resolved when the table is built, so finding an action is a single lookup.
+
+The dense table is faster, but its size is the number of states times the number of symbols, so it
+is better suited to small grammars. Either way, the generated parser works the same.
 
 ### Error Processing
 
@@ -800,6 +1248,9 @@ permitted to shift a special non-terminal symbol named `error`. It then shifts t
 and continues parsing. But the `%syntax_error` routine will not be called again until at least
 three new tokens have been successfully shifted.
 
+The values of the symbols popped from the stack are passed to their `%destructor` or
+`%token_destructor`, if any.
+
 If the parser pops its stack until the stack is empty, and it still is unable to shift the error
 symbol, then the `%parse_fail` routine is invoked and the parser fails. This is what will happen at
 the very first syntax error, of course, if there are no instances of the `error` non-terminal in
@@ -0,0 +1,21 @@
+extern crate pomelo;
+use pomelo::*;
+
+//This grammar is LR(1) but not LALR(1): the reduce/reduce conflict is resolved in favor of `e`
+pomelo! {
+    input ::= A e C;
+    input ::= A f D;
+    input ::= B f C;
+    input ::= B e D;
+    e ::= E;
+    f ::= E;
+//~^ ERROR This rule cannot be reduced
+}
+
+pomelo! {
+    %algorithm slr;
+            //~^ ERROR Unknown algorithm, expected lalr, lr1 or minimal
+    input ::=;
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+extern crate pomelo;
+use pomelo::*;
+
+//The dangling else: each conflict is explained with an example
+pomelo! {
+    input ::= stmt*;
+    stmt ::= If Cond Then stmt;
+//~^ ERROR Conflicting reduction of this rule on Else
+    stmt ::= If Cond Then stmt Else stmt;
+//~^ ERROR Shift/reduce conflict in state 5 on Else
+//~| ERROR Shift example: stmt* If Cond Then If Cond Then stmt * Else stmt
+//~| ERROR Shift derivation: stmt* If Cond Then [stmt ::= If Cond Then stmt * Else stmt]
+//~| ERROR Reduce example: stmt* If Cond Then If Cond Then stmt * Else stmt
+//~| ERROR Reduce derivation: stmt* [stmt ::= If Cond Then [stmt ::= If Cond Then stmt *] Else stmt]
+//~| ERROR Else is the look-ahead of the conflict
+    stmt ::= X;
+}
+
+fn main() {}
@@ -42,6 +42,13 @@ pomelo! {
     input ::=;
 }
 
+pomelo! {
+    %start_symbol input other input;
+                             //~^ ERROR Duplicated start symbol
+    input ::=;
+    other ::=;
+}
+
 pomelo! {
     %fallback foo Bar;
            //~^ ERROR Fallback must be a token
@@ -123,4 +130,17 @@ pomelo! {
     input ::=;
 }
 
+pomelo! {
+    %destructor input {}
+    %destructor input {}
+             //~^ ERROR Destructor already defined
+    input ::=;
+}
+
+pomelo! {
+    %destructor foo {}
+             //~^ ERROR Unknown symbol in destructor
+    input ::=;
+}
+
 fn main() {}
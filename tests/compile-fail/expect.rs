@@ -0,0 +1,27 @@
+extern crate pomelo;
+use pomelo::*;
+
+pomelo! {
+//~^ ERROR Expected 0 shift/reduce and 0 reduce/reduce conflicts, found 1 and 0
+    %expect_rr 0;
+    input ::= stmt;
+    stmt ::= If Then stmt;
+//~^ ERROR Conflicting reduction of this rule on Else
+    stmt ::= If Then stmt Else stmt;
+//~^ ERROR Shift/reduce conflict
+//~| ERROR Shift example
+//~| ERROR Shift derivation
+//~| ERROR Reduce example
+//~| ERROR Reduce derivation
+//~| ERROR Else is the look-ahead of the conflict
+    stmt ::= Other;
+}
+
+pomelo! {
+    %expect 1;
+    %expect 1;
+         //~^ ERROR Expected conflicts already defined
+    input ::= A;
+}
+
+fn main() {}
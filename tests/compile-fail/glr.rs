@@ -0,0 +1,22 @@
+extern crate pomelo;
+use pomelo::*;
+
+pomelo! {
+    %left Plus;
+    %merge expr(a, _b) { a }
+         //~^ ERROR %merge is only used with %glr
+    input ::= expr;
+    expr ::= expr Plus expr;
+    expr ::= Num;
+}
+
+pomelo! {
+    %glr;
+    %merge Num(a, _b) { a }
+         //~^ ERROR Merge symbol must be a non-terminal
+    input ::= expr;
+    expr ::= expr Plus expr;
+    expr ::= Num;
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+extern crate pomelo;
+use pomelo::*;
+
+pomelo! {
+    input ::= expr;
+    %include_grammar "tests/grammar/missing.pomelo";
+                 //~^ ERROR Cannot read
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+extern crate pomelo;
+use pomelo::*;
+
+pomelo! {
+    %include_grammar "tests/grammar/lhs_token.pomelo";
+                 //~^ ERROR tests/grammar/lhs_token.pomelo: LHS of rule must be non-terminal
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+extern crate pomelo;
+use pomelo::*;
+
+pomelo! {
+    %inline list;
+         //~^ ERROR Inline non-terminal cannot be recursive
+    input ::= list;
+    list ::= ;
+    list ::= list Item;
+}
+
+fn main() {}
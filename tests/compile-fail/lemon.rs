@@ -0,0 +1,18 @@
+extern crate pomelo;
+use pomelo::*;
+
+pomelo! {
+    %include_lemon "tests/grammar/unknown_directive.y";
+                 //~^ ERROR Unknown directive %parse_foo
+}
+
+pomelo! {
+    %include_lemon "tests/grammar/c_code.y";
+                 //~^ ERROR tests/grammar/c_code.y:2:1: %name is only used by the C code, remove it
+                 //~| ERROR tests/grammar/c_code.y:3:1: %token_type is C code, declare it in pomelo!
+                 //~| ERROR tests/grammar/c_code.y:4:1: %type is C code, declare it in pomelo!
+                 //~| ERROR tests/grammar/c_code.y:5:1: %destructor is C code, declare it in pomelo!
+                 //~| ERROR tests/grammar/c_code.y:7:1: %extra_argument is C code, declare it in pomelo!
+}
+
+fn main() {}
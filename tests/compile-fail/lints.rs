@@ -0,0 +1,74 @@
+extern crate pomelo;
+use pomelo::*;
+
+pomelo! {
+    %deny unreachable;
+    input ::= ;
+    foo ::= Tok;
+     //~^ ERROR Non-terminal is not reachable from the start symbol
+}
+
+pomelo! {
+    input ::= expr;
+  //~^ ERROR Non-terminal never derives a sequence of tokens
+    expr ::= expr Plus term;
+  //~^ ERROR Non-terminal never derives a sequence of tokens
+    term ::= Num;
+}
+
+pomelo! {
+    %deny unused_token;
+    %type Num i32;
+    %type Unused i32;
+        //~^ ERROR Token Unused is declared but not used in any rule
+    %type Spare;
+    %left Plus Minus;
+            //~^ ERROR Token Minus is declared but not used in any rule
+    input ::= expr;
+    expr ::= expr Plus expr;
+    expr ::= Num;
+}
+
+pomelo! {
+    input ::= A;
+    input ::= B;
+    input ::= A;
+  //~^ ERROR Duplicated rule
+}
+
+pomelo! {
+    input ::= stmt*;
+    stmt ::= Return Semicolon;
+    stmt ::= Break Semicolon;
+    stmt ::= Continue Semicolom;
+                     //~^ ERROR Token Semicolom is used only once, did you mean Semicolon?
+}
+
+pomelo! {
+    %allow non_productive typo;
+    input ::= expr;
+    expr ::= expr Plus term;
+    term ::= Continue Semicolom Semicolon;
+}
+
+pomelo! {
+    %allow duplicate_rule;
+    input ::= A;
+    input ::= A;
+  //~^ ERROR This rule cannot be reduced
+}
+
+pomelo! {
+    %deny unreachable typo;
+    %deny lint;
+        //~^ ERROR Unknown lint
+    input ::= ;
+}
+
+pomelo! {
+    %allow lint;
+         //~^ ERROR Unknown lint
+    input ::= ;
+}
+
+fn main() {}
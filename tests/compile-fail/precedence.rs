@@ -0,0 +1,19 @@
+extern crate pomelo;
+use pomelo::*;
+
+//%precedence has no associativity, so this conflict is not resolved
+pomelo! {
+    %type Num i64;
+    %precedence Plus;
+    input ::= expr;
+    expr ::= expr Plus expr;
+//~^ ERROR Shift/reduce conflict
+//~| ERROR Shift example: expr Plus expr * Plus expr
+//~| ERROR Shift derivation: expr Plus [expr ::= expr * Plus expr]
+//~| ERROR Reduce example: expr Plus expr * Plus expr
+//~| ERROR Reduce derivation: [expr ::= [expr ::= expr Plus expr *] Plus expr]
+//~| ERROR Plus is the look-ahead of the conflict
+    expr ::= Num;
+}
+
+fn main() {}
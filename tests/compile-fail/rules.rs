@@ -22,4 +22,24 @@ pomelo! {
                //~^ ERROR The precedence symbol must be a token
 }
 
+pomelo! {
+    input ::= A? % Comma;
+              //~^ ERROR A separated list must be repeated with `*` or `+`
+}
+
+pomelo! {
+    input ::= A* % Comma|semi;
+                       //~^ ERROR The separator must be a token
+}
+
+pomelo! {
+    input ::= (a(X) B)*;
+                 //~^ ERROR Aliases are not allowed inside a group
+}
+
+pomelo! {
+    input ::= A { }* B;
+                 //~^ ERROR A mid-rule action cannot be repeated
+}
+
 fn main() {}
@@ -0,0 +1,12 @@
+extern crate pomelo;
+use pomelo::*;
+
+pomelo! {
+    %stack_size auto;
+    input ::= list;
+    list ::= One;
+    list ::= One list;
+  //~^ ERROR %stack_size auto needs a bounded stack, but this rule can nest without limit
+}
+
+fn main() {}
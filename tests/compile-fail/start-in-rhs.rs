@@ -2,6 +2,7 @@ extern crate pomelo;
 use pomelo::*;
 
 pomelo! {
+    %allow non_productive;
     input ::= foo;
     foo ::=
         input;
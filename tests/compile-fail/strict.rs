@@ -0,0 +1,35 @@
+extern crate pomelo;
+use pomelo::*;
+
+pomelo! {
+    %strict;
+    %terminal A;
+    %nonterminal input;
+    input ::= A b;
+              //~^ ERROR Undeclared symbol, use %terminal or %nonterminal
+}
+
+pomelo! {
+    %strict;
+    %terminal A;
+    %nonterminal input;
+    %type B i32;
+        //~^ ERROR Undeclared symbol, use %terminal or %nonterminal
+    input ::= A;
+}
+
+pomelo! {
+    %terminal A;
+    %nonterminal A;
+               //~^ ERROR Symbol already declared
+    input ::= A;
+}
+
+pomelo! {
+    %terminal x;
+    input ::= A;
+    x ::= A;
+  //~^ ERROR LHS of rule must be non-terminal
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+extern crate pomelo;
+use pomelo::*;
+
+pomelo! {
+    %tables sparse;
+            //~^ ERROR Unknown tables, expected compressed or dense
+    input ::=;
+}
+
+fn main() {}
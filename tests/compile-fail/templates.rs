@@ -0,0 +1,30 @@
+extern crate pomelo;
+use pomelo::*;
+
+pomelo! {
+    input ::= list(A);
+    list(X) ::= X;
+    list(X, Y) ::= X Y;
+ //~^ ERROR Wrong number of parameters
+}
+
+pomelo! {
+    input ::= list(A, B);
+            //~^ ERROR Wrong number of arguments
+    list(X) ::= X;
+}
+
+pomelo! {
+    input ::= x(A);
+    x(X) ::= x(y(X));
+          //~^ ERROR Parameterized non-terminal expands infinitely
+    y(X) ::= X;
+}
+
+pomelo! {
+    input ::= list;
+    list(X) ::= X;
+ //~^ ERROR Non-terminal with parameters cannot be used without them
+}
+
+fn main() {}
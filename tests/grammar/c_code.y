@@ -0,0 +1,13 @@
+//Used in tests/compile-fail/lemon.rs
+%name Calc
+%token_type { int }
+%type expr { int }
+%destructor expr { free_expr($$); }
+%ifdef POMELO
+%extra_argument { Context *ctx }
+%endif
+%ifndef POMELO
+%syntax_error { fprintf(stderr, "syntax error\n"); }
+%endif
+input ::= expr.
+expr ::= A.
@@ -0,0 +1,41 @@
+/* A Lemon grammar, imported in tests/pass/lemon.rs */
+%ifndef POMELO
+%name Calc
+%token_prefix TK_
+%token_type { int }
+%include {
+#include <assert.h>
+#include "calc.h"
+}
+%syntax_error {
+    fprintf(stderr, "syntax error: '}'\n");
+}
+%endif
+
+%left PLUS MINUS.
+%left TIMES DIVIDE.
+%right NEG.
+
+%ifndef POMELO
+%type expr { int }
+%destructor expr { free_expr($$); }
+%endif
+%token_class number INT|FLOAT.
+
+program ::= expr(A). { printf("%d\n", A); }
+
+expr(A) ::= expr(B) PLUS expr(C).   { A = B + C; }
+expr(A) ::= expr(B) MINUS expr(C).  { A = B - C; }
+expr(A) ::= expr(B) TIMES expr(C).  { A = B * C; }
+expr(A) ::= expr(B) DIVIDE expr(C). {
+    if (C != 0) { A = B / C; } else { A = 0; /* } */ }
+}
+expr(A) ::= MINUS expr(B). [NEG]    { A = -B; }
+expr(A) ::= LPAREN expr(B) RPAREN.  { A = B; }
+expr(A) ::= number(B).              { A = B; }
+
+%ifdef WITH_POWER
+expr(A) ::= expr(B) POWER expr(C).  { A = pow(B, C); }
+%else
+expr ::= NOTHING.
+%endif
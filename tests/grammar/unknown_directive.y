@@ -0,0 +1,3 @@
+//Used in tests/compile-fail/lemon.rs
+input ::= A.
+%parse_foo { }
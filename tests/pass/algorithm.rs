@@ -0,0 +1,76 @@
+//This grammar is LR(1) but not LALR(1): the states after `A E` and `B E` have the same core, but
+//merging them would make a reduce/reduce conflict between `e ::= E` and `f ::= E`.
+mod lr1 {
+    use pomelo::*;
+
+    pomelo! {
+        %algorithm lr1;
+        %type input &'static str;
+
+        input ::= A e C { "aec" }
+        input ::= A f D { "afd" }
+        input ::= B f C { "bfc" }
+        input ::= B e D { "bed" }
+        e ::= E;
+        f ::= E;
+    }
+
+    use parser::*;
+
+    fn parse(tokens: Vec<Token>) -> Result<&'static str, ()> {
+        let mut p = Parser::new();
+        for tok in tokens {
+            p.parse(tok)?;
+        }
+        p.end_of_input()
+    }
+
+    #[test]
+    fn no_conflicts() -> Result<(), ()> {
+        use Token::*;
+        assert_eq!(parse(vec![A, E, C])?, "aec");
+        assert_eq!(parse(vec![A, E, D])?, "afd");
+        assert_eq!(parse(vec![B, E, C])?, "bfc");
+        assert_eq!(parse(vec![B, E, D])?, "bed");
+        assert!(parse(vec![A, E, E]).is_err());
+        Ok(())
+    }
+}
+
+//The same grammar, with the LALR(1) states split only where they would conflict
+mod minimal {
+    use pomelo::*;
+
+    pomelo! {
+        %algorithm minimal;
+        %type input &'static str;
+
+        input ::= A e C { "aec" }
+        input ::= A f D { "afd" }
+        input ::= B f C { "bfc" }
+        input ::= B e D { "bed" }
+        e ::= E;
+        f ::= E;
+    }
+
+    use parser::*;
+
+    fn parse(tokens: Vec<Token>) -> Result<&'static str, ()> {
+        let mut p = Parser::new();
+        for tok in tokens {
+            p.parse(tok)?;
+        }
+        p.end_of_input()
+    }
+
+    #[test]
+    fn no_conflicts() -> Result<(), ()> {
+        use Token::*;
+        assert_eq!(parse(vec![A, E, C])?, "aec");
+        assert_eq!(parse(vec![A, E, D])?, "afd");
+        assert_eq!(parse(vec![B, E, C])?, "bfc");
+        assert_eq!(parse(vec![B, E, D])?, "bed");
+        assert!(parse(vec![A, E, E]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use pomelo::*;
+
+pomelo! {
+    %module discard;
+    %extra_argument Vec<String>;
+    %error Vec<String>;
+    %syntax_error { Ok(()) }
+    %parse_fail { std::mem::take(extra) }
+    %token #[derive(Debug)] pub enum Token {};
+    %type Num i32;
+    %type expr i32;
+    %left Plus;
+
+    %destructor expr(e) { extra.push(format!("expr {}", e)); }
+    %token_destructor { extra.push(format!("{:?}", token)); }
+
+    input ::= stmt*;
+    stmt ::= expr(e) Semicolon { extra.push(format!("= {}", e)); }
+    expr ::= Num;
+    expr ::= expr(a) Plus expr(b) { a + b }
+}
+
+pomelo! {
+    %module recover;
+    %extra_argument Vec<String>;
+    %syntax_error {
+        extra.push(String::from("error"));
+        Ok(())
+    }
+    %token #[derive(Debug)] pub enum Token {};
+    %type Num i32;
+    %type expr i32;
+    %left Plus;
+
+    %destructor expr(e) { extra.push(format!("expr {}", e)); }
+    %token_destructor { extra.push(format!("{:?}", token)); }
+
+    input ::= stmt*;
+    stmt ::= expr(e) Semicolon { extra.push(format!("= {}", e)); }
+    stmt ::= error Semicolon { extra.push(String::from("recovered")); }
+    expr ::= Num;
+    expr ::= expr(a) Plus expr(b) { a + b }
+}
+
+#[test]
+fn parse_failed() {
+    use discard::*;
+    use Token::*;
+    let mut p = Parser::new(Vec::new());
+    for tok in [
+        Num(1), Plus, Num(2), Semicolon,
+        //the second Semicolon is discarded after the syntax error
+        Num(5), Plus, Semicolon, Semicolon, Num(3), Plus,
+    ] {
+        p.parse(tok).unwrap();
+    }
+    //the stack is emptied top to bottom
+    let log = p.end_of_input().unwrap_err();
+    assert_eq!(log, vec!["= 3", "Semicolon", "Plus", "expr 8"]);
+}
+
+#[test]
+fn error_recovery() {
+    use recover::*;
+    use Token::*;
+    let mut p = Parser::new(Vec::new());
+    for tok in [
+        Num(1), Plus, Num(2), Num(4), Plus, Semicolon,
+        Num(5), Semicolon,
+    ] {
+        p.parse(tok).unwrap();
+    }
+    let ((), log) = p.end_of_input().unwrap();
+    assert_eq!(log, vec!["expr 3", "error", "Plus", "recovered", "= 5"]);
+}
@@ -0,0 +1,36 @@
+use pomelo::*;
+
+pomelo! {
+    %extra_argument String;
+    %syntax_error { extra.push('X'); Ok(()) }
+
+    start ::= items;
+    items ::= item;
+    items ::= items Comma item;
+
+    //The error symbol is shifted and reduced at once
+    item ::= error { extra.push('E'); }
+    item ::= One Two { extra.push('1'); }
+}
+
+#[test]
+fn error() -> Result<(), ()> {
+    use parser::*;
+    use Token::*;
+
+    let mut p = Parser::new(String::new());
+
+    for t in vec![
+        One, Two, Comma,
+        Two, Comma,
+        One, Two, Comma,
+        One, One, Comma,
+        One, Two,
+    ] {
+        p.parse(t)?;
+    }
+    let ((), extra) = p.end_of_input()?;
+    assert_eq!(extra, "1XE1XE1");
+
+    Ok(())
+}
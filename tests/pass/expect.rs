@@ -0,0 +1,26 @@
+use pomelo::*;
+
+pomelo! {
+    //The dangling else: the conflict is resolved with a shift, so `Else` goes with the nearest `If`
+    %expect 1;
+    %type input String;
+    %type stmt String;
+
+    input ::= stmt;
+    stmt ::= If Then stmt(s) { format!("if({})", s) }
+    stmt ::= If Then stmt(s) Else stmt(e) { format!("if({}, {})", s, e) }
+    stmt ::= Other { String::from("x") }
+}
+
+use parser::*;
+
+#[test]
+fn dangling_else() -> Result<(), ()> {
+    use Token::*;
+    let mut p = Parser::new();
+    for tok in [If, Then, If, Then, Other, Else, Other] {
+        p.parse(tok)?;
+    }
+    assert_eq!(p.end_of_input()?, "if(if(x, x))");
+    Ok(())
+}
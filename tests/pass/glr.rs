@@ -0,0 +1,280 @@
+mod all {
+    use pomelo::*;
+
+    pomelo! {
+        %glr;
+        %type input String;
+        %type expr String;
+        %type Num i32;
+
+        input ::= expr;
+        expr ::= expr(a) Plus expr(b) { format!("({}+{})", a, b) }
+        expr ::= Num(n) { n.to_string() }
+    }
+
+    use parser::*;
+
+    #[test]
+    fn ambiguous() -> Result<(), ()> {
+        use Token::*;
+        let mut p = Parser::new();
+        for tok in [Num(1), Plus, Num(2), Plus, Num(3)] {
+            p.parse(tok)?;
+        }
+        let mut res = p.end_of_input()?;
+        res.sort();
+        assert_eq!(res, ["((1+2)+3)", "(1+(2+3))"]);
+        Ok(())
+    }
+
+    #[test]
+    fn all_trees() -> Result<(), ()> {
+        use Token::*;
+        let mut p = Parser::new();
+        for tok in [Num(1), Plus, Num(2), Plus, Num(3), Plus, Num(4)] {
+            p.parse(tok)?;
+        }
+        let mut res = p.end_of_input()?;
+        res.sort();
+        res.dedup();
+        assert_eq!(res.len(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn unambiguous() -> Result<(), ()> {
+        use Token::*;
+        let mut p = Parser::new();
+        for tok in [Num(1), Plus, Num(2)] {
+            p.parse(tok)?;
+        }
+        assert_eq!(p.end_of_input()?, ["(1+2)"]);
+        Ok(())
+    }
+
+    #[test]
+    fn error() {
+        use Token::*;
+        let mut p = Parser::new();
+        assert!(p.parse(Num(1)).is_ok());
+        assert!(p.parse(Plus).is_ok());
+        assert!(p.parse(Plus).is_err());
+
+        //Every branch fails while the parser is split
+        let mut p = Parser::new();
+        for tok in [Num(1), Plus, Num(2), Plus] {
+            assert!(p.parse(tok).is_ok());
+        }
+        assert!(p.parse(Plus).is_err());
+    }
+}
+
+mod merge {
+    use pomelo::*;
+
+    pomelo! {
+        %glr;
+        %extra_argument usize;
+        %type input (i32, usize);
+        %type expr (i32, usize);
+        %type Num i32;
+        //Count the ambiguities and keep any of the values: they all add the same
+        %merge expr(a, _b) {
+            *extra += 1;
+            a
+        }
+
+        input ::= expr;
+        expr ::= expr(a) Plus expr(b) { (a.0 + b.0, a.1 + b.1 + 1) }
+        expr ::= Num(n) { (n, 0) }
+    }
+
+    use parser::*;
+
+    #[test]
+    fn merged() -> Result<(), ()> {
+        use Token::*;
+        let mut p = Parser::new(0);
+        for tok in [Num(1), Plus, Num(2), Plus, Num(3), Plus, Num(4)] {
+            p.parse(tok)?;
+        }
+        let (res, merges) = p.end_of_input()?;
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0], (10, 3));
+        assert!(merges > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn long() -> Result<(), ()> {
+        use Token::*;
+        //The branches share their nodes, so this does not grow exponentially
+        let mut p = Parser::new(0);
+        p.parse(Num(0))?;
+        for n in 1..=40 {
+            p.parse(Plus)?;
+            p.parse(Num(n))?;
+        }
+        let (res, _) = p.end_of_input()?;
+        assert_eq!(res, [(820, 40)]);
+        Ok(())
+    }
+}
+
+mod deferred {
+    use pomelo::*;
+
+    pomelo! {
+        %glr;
+        %extra_argument Vec<String>;
+        %error String;
+        %type Num i32;
+        %type Name String;
+        %type a i32;
+        %type b i32;
+        %destructor Name(s) { extra.push(format!("drop {}", s)); }
+
+        input ::= a Dot Name;
+        input ::= b Dot Name Name;
+        a ::= Num(n) {
+            if n < 0 {
+                return Err(String::from("negative"));
+            }
+            extra.push(format!("a {}", n));
+            n
+        }
+        b ::= Num(n) {
+            if n < 0 {
+                return Err(String::from("negative"));
+            }
+            extra.push(format!("b {}", n));
+            n
+        }
+    }
+
+    use parser::*;
+
+    fn parse(p: &mut Parser, tokens: Vec<Token>) -> Result<(), String> {
+        for tok in tokens {
+            p.parse(tok)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn once() -> Result<(), String> {
+        use Token::*;
+        //Only the code of the branch that is left is run
+        let mut p = Parser::new(Vec::new());
+        parse(&mut p, vec![Num(1), Dot, Name("x".into())])?;
+        assert!(p.extra().is_empty());
+        let (_, log) = p.end_of_input()?;
+        assert_eq!(log, ["a 1"]);
+
+        let mut p = Parser::new(Vec::new());
+        parse(&mut p, vec![Num(2), Dot, Name("x".into()), Name("y".into())])?;
+        assert_eq!(p.extra(), &["b 2"]);
+        let (_, log) = p.end_of_input()?;
+        assert_eq!(log, ["b 2"]);
+        Ok(())
+    }
+
+    #[test]
+    fn destroyed() {
+        use Token::*;
+        //An error in a deferred rule fails the parse, and the values left are dropped
+        let mut p = Parser::new(Vec::new());
+        let res = parse(&mut p, vec![Num(-1), Dot, Name("x".into()), Name("y".into())]);
+        assert_eq!(res.unwrap_err(), "negative");
+        let mut log = p.extra().clone();
+        log.sort();
+        assert_eq!(log, ["drop x", "drop y"]);
+    }
+}
+
+mod alternatives {
+    use pomelo::*;
+
+    pomelo! {
+        %glr;
+        %error String;
+        %type input i32;
+        %type expr i32;
+        %type Num i32;
+
+        input ::= expr;
+        expr ::= expr(a) Div expr(b) {
+            if b == 0 {
+                return Err(String::from("division by zero"));
+            }
+            a / b
+        }
+        expr ::= Num(n) { n }
+    }
+
+    use parser::*;
+
+    fn parse(nums: &[i32]) -> Result<Vec<i32>, String> {
+        use Token::*;
+        let mut p = Parser::new();
+        p.parse(Num(nums[0]))?;
+        for &n in &nums[1..] {
+            p.parse(Div)?;
+            p.parse(Num(n))?;
+        }
+        let mut res = p.end_of_input()?;
+        res.sort();
+        Ok(res)
+    }
+
+    #[test]
+    fn failed() -> Result<(), String> {
+        //An alternative that fails is dropped...
+        assert_eq!(parse(&[8, 2, 1])?, [4, 4]);
+        assert_eq!(parse(&[1, 2, 4])?, [0]);
+        //...unless every one fails
+        assert_eq!(parse(&[2, 0, 1]).unwrap_err(), "division by zero");
+        Ok(())
+    }
+}
+
+mod no_clone {
+    use pomelo::*;
+
+    //A value that cannot be cloned
+    #[derive(Debug, PartialEq)]
+    pub struct Expr(String);
+
+    pomelo! {
+        %glr;
+        %include { use super::Expr; }
+        %type input Expr;
+        %type expr Expr;
+        %type Num i32;
+
+        input ::= expr;
+        expr ::= expr(a) Plus expr(b) { Expr(format!("({}+{})", a.0, b.0)) }
+        expr ::= Num(n) { Expr(n.to_string()) }
+    }
+
+    use parser::*;
+
+    #[test]
+    fn shared() -> Result<(), ()> {
+        use Token::*;
+        //Unambiguous values do not need Clone
+        let mut p = Parser::new();
+        for tok in [Num(1), Plus, Num(2)] {
+            p.parse(tok)?;
+        }
+        assert_eq!(p.end_of_input()?, [Expr("(1+2)".into())]);
+
+        //A value used by two alternatives goes to the last one
+        let mut p = Parser::new();
+        for tok in [Num(1), Plus, Num(2), Plus, Num(3)] {
+            p.parse(tok)?;
+        }
+        assert_eq!(p.end_of_input()?.len(), 1);
+        Ok(())
+    }
+}
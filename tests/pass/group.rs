@@ -0,0 +1,92 @@
+use pomelo::*;
+
+pomelo! {
+    %type input Vec<String>;
+    %type Num i32;
+    %type Ident String;
+    %type value String;
+
+    input ::= (item Semicolon)*;
+    %type item String;
+    //The sign of the operator is its value
+    %type Plus i32;
+    %type Minus i32;
+    //A group with several typed symbols is a tuple, the compound is one of them
+    item ::= Sum Num(a) ((Plus | Minus) Num)*(r) {
+        (a + r.iter().map(|(sign, n)| sign * n).sum::<i32>()).to_string()
+    }
+    //A group with a single typed symbol has its type
+    item ::= Count Num(a) (Comma Num)*(r) { (a + r.iter().sum::<i32>()).to_string() }
+    //Different types make an AltN enum
+    item ::= Print (Num | Ident)(x) {
+        match x {
+            Alt2::A(n) => format!("num {}", n),
+            Alt2::B(s) => format!("ident {}", s),
+        }
+    }
+    //A group with several typed symbols is a tuple
+    item ::= Let (Ident Eq value)?(x) {
+        match x {
+            Some((id, v)) => format!("{} = {}", id, v),
+            None => String::from("nothing"),
+        }
+    }
+    //Equal types in all alternatives
+    item ::= Show (value | LParen value RParen)(v) { v }
+    //Only tokens: a plain compound
+    item ::= Keyword (Sum | Print | Let) { String::from("keyword") }
+    value ::= Num(n) { n.to_string() }
+    value ::= Ident(s) { s }
+}
+
+use parser::*;
+
+fn parse(tokens: Vec<Token>) -> Result<Vec<String>, ()> {
+    let mut parse = Parser::new();
+    for tok in tokens {
+        parse.parse(tok)?;
+    }
+    parse.end_of_input()
+}
+
+fn id(s: &str) -> Token {
+    Token::Ident(s.to_string())
+}
+
+#[test]
+fn repeated_group() -> Result<(), ()> {
+    use Token::*;
+    let res = parse(vec![
+        Sum, Num(1), Semicolon,
+        Sum, Num(1), Plus(1), Num(2), Minus(-1), Num(3), Semicolon,
+        Count, Num(1), Comma, Num(2), Comma, Num(3), Semicolon,
+    ])?;
+    assert_eq!(res, vec!["1", "0", "6"]);
+    Ok(())
+}
+
+#[test]
+fn alternatives() -> Result<(), ()> {
+    use Token::*;
+    let res = parse(vec![
+        Print, Num(1), Semicolon,
+        Print, id("a"), Semicolon,
+        Show, Num(2), Semicolon,
+        Show, LParen, id("b"), RParen, Semicolon,
+        Keyword, Print, Semicolon,
+    ])?;
+    assert_eq!(res, vec!["num 1", "ident a", "2", "b", "keyword"]);
+    assert!(parse(vec![Show, LParen, Num(2), Semicolon]).is_err());
+    Ok(())
+}
+
+#[test]
+fn tuple() -> Result<(), ()> {
+    use Token::*;
+    let res = parse(vec![
+        Let, Semicolon,
+        Let, id("x"), Eq, Num(3), Semicolon,
+    ])?;
+    assert_eq!(res, vec!["nothing", "x = 3"]);
+    Ok(())
+}
@@ -0,0 +1,21 @@
+use pomelo::*;
+
+pomelo! {
+    %type input i64;
+    input ::= expr;
+    %include_grammar "tests/grammar/expr.pomelo";
+}
+
+use parser::*;
+
+#[test]
+fn include_grammar() -> Result<(), ()> {
+    use Token::*;
+    let mut parse = Parser::new();
+    for tok in vec![Num(2), Plus, Num(3), Times, LParen, Num(4), Minus, Num(1), RParen] {
+        parse.parse(tok)?;
+    }
+    let res = parse.end_of_input()?;
+    assert_eq!(res, 11);
+    Ok(())
+}
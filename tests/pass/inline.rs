@@ -0,0 +1,71 @@
+use pomelo::*;
+
+pomelo! {
+    //Without `%inline`, `binop` would hide the precedence of the operators
+    %inline binop;
+    %type Num i64;
+    %type input i64;
+    %type expr i64;
+    %type binop fn(i64, i64) -> i64;
+    %left Plus Minus;
+    %left Times;
+
+    input ::= expr;
+    expr ::= Num;
+    expr ::= expr(a) binop(op) expr(b) { op(a, b) }
+    binop ::= Plus { |a, b| a + b }
+    binop ::= Minus { |a, b| a - b }
+    binop ::= Times { |a, b| a * b }
+}
+
+use parser::*;
+
+fn parse(tokens: Vec<Token>) -> Result<i64, ()> {
+    let mut p = Parser::new();
+    for tok in tokens {
+        p.parse(tok)?;
+    }
+    p.end_of_input()
+}
+
+#[test]
+fn operators() -> Result<(), ()> {
+    use Token::*;
+    assert_eq!(parse(vec![Num(1), Plus, Num(2), Times, Num(3)])?, 7);
+    assert_eq!(parse(vec![Num(2), Times, Num(3), Plus, Num(1)])?, 7);
+    assert_eq!(parse(vec![Num(7), Minus, Num(2), Minus, Num(1)])?, 4);
+    Ok(())
+}
+
+mod aliases {
+    use pomelo::*;
+
+    pomelo! {
+        //The aliases of the inline rules do not clash with those of the outer rule
+        %inline sign;
+        %type Num i64;
+        %type input i64;
+        %type sign i64;
+
+        input ::= sign(a) Num(n) { a * n }
+        sign ::= { 1 }
+        sign ::= Minus Num(n) { -n }
+    }
+
+    use parser::*;
+
+    #[test]
+    fn sign() -> Result<(), ()> {
+        use Token::*;
+        let mut p = Parser::new();
+        p.parse(Num(3))?;
+        assert_eq!(p.end_of_input()?, 3);
+
+        let mut p = Parser::new();
+        for tok in [Minus, Num(2), Num(3)] {
+            p.parse(tok)?;
+        }
+        assert_eq!(p.end_of_input()?, -6);
+        Ok(())
+    }
+}
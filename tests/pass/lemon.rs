@@ -0,0 +1,39 @@
+use pomelo::*;
+
+pomelo! {
+    %include_lemon "tests/grammar/calc.y" "tests/grammar/calc_actions.pomelo";
+}
+
+use parser::*;
+
+fn parse(tokens: Vec<Token>) -> Result<i32, ()> {
+    let mut parse = Parser::new();
+    for tok in tokens {
+        parse.parse(tok)?;
+    }
+    parse.end_of_input()
+}
+
+#[test]
+fn lemon_grammar() -> Result<(), ()> {
+    use Token::*;
+    let res = parse(vec![
+        INT(2), PLUS, MINUS, INT(3), TIMES, LPAREN, FLOAT(4), MINUS, INT(1), RPAREN,
+    ])?;
+    assert_eq!(res, -7);
+    Ok(())
+}
+
+#[test]
+#[should_panic(expected = "tests/grammar/calc.y:30: expr ::= expr DIVIDE expr")]
+fn lemon_stub() {
+    use Token::*;
+    let _ = parse(vec![INT(2), DIVIDE, INT(3)]);
+}
+
+#[test]
+fn lemon_ifdef() -> Result<(), ()> {
+    use Token::*;
+    assert_eq!(parse(vec![NOTHING])?, 0);
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use pomelo::*;
+
+pomelo! {
+    %extra_argument Vec<String>;
+    %type Ident String;
+    %type input Vec<String>;
+    %type item String;
+
+    input ::= item*;
+    //The scope is opened as soon as `LBrace` is seen, before the items inside are parsed
+    item ::= LBrace { extra.push(String::from("open")); extra.len() }(n: usize) item*(items) RBrace {
+        extra.push(String::from("close"));
+        format!("{}:{}", n, items.join(","))
+    }
+    item ::= Ident(s) { extra.push(s.clone()); s }
+    //An action without a type has no value
+    item ::= Let { extra.push(String::from("let")); } Ident(s) { s }
+}
+
+use parser::*;
+
+#[test]
+fn mid_rule() -> Result<(), ()> {
+    use Token::*;
+    let mut p = Parser::new(Vec::new());
+    for tok in [
+        Ident("a".to_string()),
+        LBrace, Ident("b".to_string()), RBrace,
+        Let, Ident("c".to_string()),
+    ] {
+        p.parse(tok)?;
+    }
+    let (res, log) = p.end_of_input()?;
+    assert_eq!(res, vec!["a", "2:b", "c"]);
+    assert_eq!(log, vec!["a", "open", "b", "close", "let"]);
+    Ok(())
+}
@@ -2,6 +2,7 @@ mod pm_lexer;
 mod toy_lexer;
 mod tree;
 mod start_symbol;
+mod start_symbols;
 mod token;
 mod default_type;
 mod fallback;
@@ -14,8 +15,24 @@ mod error1;
 mod error2;
 mod error3;
 mod error4;
+mod error5;
+mod destructor;
 mod auto_rule;
 mod optional;
+mod repeat;
+mod separated;
+mod group;
+mod template;
+mod mid_rule;
+mod expect;
+mod precedence;
+mod inline;
+mod algorithm;
+mod tables;
+mod strict;
+mod glr;
+mod include_grammar;
+mod lemon;
 mod extra_token;
 mod typed_error;
 mod stack;
@@ -0,0 +1,44 @@
+use pomelo::*;
+
+pomelo! {
+    %type Num i64;
+    %type input i64;
+    %type expr i64;
+    %left Plus Minus;
+    %left Times;
+    %precedence UMinus;
+
+    input ::= expr;
+    expr ::= Num;
+    expr ::= expr(a) Plus expr(b) { a + b }
+    expr ::= expr(a) Minus expr(b) { a - b }
+    expr ::= expr(a) Times expr(b) { a * b }
+    expr ::= Minus expr(a) [UMinus] { -a }
+}
+
+use parser::*;
+
+//`UMinus` is only used for precedence, so it is not a token
+#[allow(dead_code)]
+fn all_tokens(t: Token) {
+    match t {
+        Token::Num(_) | Token::Plus | Token::Minus | Token::Times => {}
+    }
+}
+
+fn parse(tokens: Vec<Token>) -> Result<i64, ()> {
+    let mut p = Parser::new();
+    for tok in tokens {
+        p.parse(tok)?;
+    }
+    p.end_of_input()
+}
+
+#[test]
+fn unary_minus() -> Result<(), ()> {
+    use Token::*;
+    assert_eq!(parse(vec![Minus, Num(2), Times, Num(3)])?, -6);
+    assert_eq!(parse(vec![Minus, Num(2), Minus, Num(3)])?, -5);
+    assert_eq!(parse(vec![Num(1), Minus, Minus, Num(2), Times, Num(3)])?, 7);
+    Ok(())
+}
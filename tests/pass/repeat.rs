@@ -0,0 +1,52 @@
+use pomelo::*;
+
+pomelo! {
+    %type input (Vec<i32>, Vec<i32>);
+    %type Number i32;
+    %type Word String;
+
+    input ::= LBracket Number*(A) RBracket LParen Number+(B) RParen Word* { (A, B) }
+}
+
+use parser::*;
+
+#[test]
+fn zero_or_more() -> Result<(), ()> {
+    let mut parse = Parser::new();
+    parse.parse(Token::LBracket)?;
+    parse.parse(Token::RBracket)?;
+    parse.parse(Token::LParen)?;
+    parse.parse(Token::Number(1))?;
+    parse.parse(Token::RParen)?;
+    let res = parse.end_of_input()?;
+    assert_eq!(res, (vec![], vec![1]));
+    Ok(())
+}
+
+#[test]
+fn one_or_more() -> Result<(), ()> {
+    let mut parse = Parser::new();
+    parse.parse(Token::LBracket)?;
+    parse.parse(Token::Number(1))?;
+    parse.parse(Token::Number(2))?;
+    parse.parse(Token::RBracket)?;
+    parse.parse(Token::LParen)?;
+    parse.parse(Token::Number(3))?;
+    parse.parse(Token::Number(4))?;
+    parse.parse(Token::Number(5))?;
+    parse.parse(Token::RParen)?;
+    parse.parse(Token::Word("a".to_string()))?;
+    parse.parse(Token::Word("b".to_string()))?;
+    let res = parse.end_of_input()?;
+    assert_eq!(res, (vec![1, 2], vec![3, 4, 5]));
+    Ok(())
+}
+
+#[test]
+fn one_or_more_empty() {
+    let mut parse = Parser::new();
+    parse.parse(Token::LBracket).unwrap();
+    parse.parse(Token::RBracket).unwrap();
+    parse.parse(Token::LParen).unwrap();
+    assert!(parse.parse(Token::RParen).is_err());
+}
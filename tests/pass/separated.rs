@@ -0,0 +1,60 @@
+use pomelo::*;
+
+pomelo! {
+    %type input Vec<Vec<String>>;
+    %type Ident String;
+
+    input ::= list* % Semicolon;
+    list ::= LParen Ident % Comma(A) RParen { A }
+    list ::= LBracket Ident* % Comma?(A) RBracket { A }
+    %type list Vec<String>;
+}
+
+use parser::*;
+
+fn parse(tokens: Vec<Token>) -> Result<Vec<Vec<String>>, ()> {
+    let mut parse = Parser::new();
+    for tok in tokens {
+        parse.parse(tok)?;
+    }
+    parse.end_of_input()
+}
+
+fn id(s: &str) -> Token {
+    Token::Ident(s.to_string())
+}
+
+#[test]
+fn separated() -> Result<(), ()> {
+    use Token::*;
+    assert_eq!(parse(vec![])?, Vec::<Vec<String>>::new());
+    let res = parse(vec![
+        LParen, id("a"), Comma, id("b"), RParen,
+        Semicolon,
+        LParen, id("c"), RParen,
+    ])?;
+    assert_eq!(res, vec![vec!["a", "b"], vec!["c"]]);
+    Ok(())
+}
+
+#[test]
+fn trailing() -> Result<(), ()> {
+    use Token::*;
+    let res = parse(vec![
+        LBracket, RBracket,
+        Semicolon,
+        LBracket, id("a"), Comma, RBracket,
+        Semicolon,
+        LBracket, id("b"), Comma, id("c"), RBracket,
+    ])?;
+    assert_eq!(res, vec![vec![], vec!["a"], vec!["b", "c"]]);
+    Ok(())
+}
+
+#[test]
+fn not_trailing() {
+    use Token::*;
+    assert!(parse(vec![LParen, id("a"), Comma, RParen]).is_err());
+    assert!(parse(vec![LParen, RParen]).is_err());
+    assert!(parse(vec![LBracket, RBracket, Semicolon]).is_err());
+}
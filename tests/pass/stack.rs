@@ -72,3 +72,44 @@ fn stack() -> Result<(), ()> {
     Ok(())
     
 }
+
+#[test]
+fn unbounded() {
+    assert_eq!(MAX_STACK_DEPTH, None);
+}
+
+mod auto {
+    use pomelo::*;
+
+    pomelo! {
+        //Only left recursion, so the stack is bounded
+        %stack_size auto;
+        %type Num i32;
+        %type input i32;
+        %type sum i32;
+        %type term i32;
+        input ::= sum;
+        sum ::= sum(a) Plus term(b) { a + b }
+        sum ::= term;
+        term ::= term(a) Times Num(b) { a * b }
+        term ::= Num;
+    }
+
+    use parser::*;
+
+    #[test]
+    fn auto() -> Result<(), ()> {
+        use Token::*;
+        //The start state, then `sum Plus term Times Num`
+        assert_eq!(MAX_STACK_DEPTH, Some(6));
+        let mut p = Parser::new();
+        p.parse(Num(1))?;
+        for _ in 0..100 {
+            for tok in [Plus, Num(2), Times, Num(3), Times, Num(1)] {
+                p.parse(tok)?;
+            }
+        }
+        assert_eq!(p.end_of_input()?, 601);
+        Ok(())
+    }
+}
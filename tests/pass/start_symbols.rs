@@ -0,0 +1,119 @@
+mod simple {
+    use pomelo::*;
+
+    pomelo! {
+        //`program` is the main start symbol, `expr` and `type_expr` get their own constructors
+        %start_symbol program expr type_expr;
+        %type program Vec<i32>;
+        %type stmt i32;
+        %type expr i32;
+        %type type_expr String;
+        %type Num i32;
+        %type Name String;
+        %left Plus;
+
+        program ::= stmt* ;
+        stmt ::= Let Name Colon type_expr Eq expr(e) Semi { e }
+        expr ::= expr(a) Plus expr(b) { a + b }
+        expr ::= Num;
+        expr ::= LParen expr RParen;
+        type_expr ::= Name;
+        type_expr ::= Name(n) LParen type_expr(t) RParen { format!("{}<{}>", n, t) }
+    }
+
+    use parser::*;
+
+    #[test]
+    fn program() -> Result<(), ()> {
+        use Token::*;
+        let mut p = Parser::new();
+        let toks = [
+            Let,
+            Name("x".into()),
+            Colon,
+            Name("i32".into()),
+            Eq,
+            Num(1),
+            Plus,
+            Num(2),
+            Semi,
+        ];
+        for tok in toks {
+            p.parse(tok)?;
+        }
+        assert_eq!(p.end_of_input()?, [3]);
+        Ok(())
+    }
+
+    #[test]
+    fn expr() -> Result<(), ()> {
+        use Token::*;
+        let mut p = Parser::new_expr();
+        for tok in [Num(1), Plus, LParen, Num(2), Plus, Num(3), RParen] {
+            p.parse(tok)?;
+        }
+        assert_eq!(p.end_of_input()?, 6);
+        Ok(())
+    }
+
+    #[test]
+    fn type_expr() -> Result<(), ()> {
+        use Token::*;
+        let mut p: TypeExprParser = Parser::new_type_expr();
+        for tok in [Name("Vec".into()), LParen, Name("i32".into()), RParen] {
+            p.parse(tok)?;
+        }
+        assert_eq!(p.end_of_input()?, "Vec<i32>");
+        Ok(())
+    }
+
+    #[test]
+    fn wrong_start() {
+        use Token::*;
+        //A statement is not an expression
+        let mut p = Parser::new_expr();
+        assert!(p.parse(Let).is_err());
+
+        //Nor is an expression followed by anything else
+        let mut p = Parser::new_expr();
+        assert!(p.parse(Num(1)).is_ok());
+        assert!(p.parse(Num(2)).is_err());
+    }
+}
+
+mod extra {
+    use pomelo::*;
+
+    pomelo! {
+        %extra_argument Vec<i32>;
+        %start_symbol list item;
+        %type item i32;
+        %type Num i32;
+
+        list ::= item+ ;
+        item ::= Num(n) { extra.push(n); n }
+    }
+
+    use parser::*;
+
+    #[test]
+    fn item() -> Result<(), ()> {
+        let mut p = Parser::new_item(Vec::new());
+        p.extra_mut().push(0);
+        p.parse(Token::Num(42))?;
+        let (item, extra) = p.end_of_input()?;
+        assert_eq!(item, 42);
+        assert_eq!(extra, [0, 42]);
+        Ok(())
+    }
+
+    #[test]
+    fn list() -> Result<(), ()> {
+        let mut p = Parser::new(Vec::new());
+        p.parse(Token::Num(1))?;
+        p.parse(Token::Num(2))?;
+        let ((), extra) = p.end_of_input()?;
+        assert_eq!(extra, [1, 2]);
+        Ok(())
+    }
+}
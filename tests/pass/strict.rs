@@ -0,0 +1,32 @@
+use pomelo::*;
+
+pomelo! {
+    //Every symbol is declared, so the names need not follow the case convention
+    %strict;
+    %terminal num plus times lparen rparen;
+    %nonterminal Input Expr;
+    %type num i32;
+    %type Input i32;
+    %type Expr i32;
+    %left plus;
+    %left times;
+
+    Input ::= Expr;
+    Expr ::= num;
+    Expr ::= Expr(a) plus Expr(b) { a + b }
+    Expr ::= Expr(a) times Expr(b) { a * b }
+    Expr ::= lparen Expr rparen;
+}
+
+use parser::*;
+
+#[test]
+fn strict() -> Result<(), ()> {
+    use Token::*;
+    let mut p = Parser::new();
+    for tok in [num(2), times, lparen, num(3), plus, num(4), rparen] {
+        p.parse(tok)?;
+    }
+    assert_eq!(p.end_of_input()?, 14);
+    Ok(())
+}
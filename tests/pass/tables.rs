@@ -0,0 +1,117 @@
+//The same grammar with both kinds of tables: the fallback token, the wildcard and the error
+//recovery must work the same with the dense tables, where they are resolved beforehand.
+mod compressed {
+    use pomelo::*;
+
+    pomelo! {
+        %tables compressed;
+        %syntax_error { Ok(()) }
+        %fallback Name Let;
+        %wildcard Any;
+        %left Plus;
+        %left Times;
+        %type input Vec<i32>;
+        %type stmt i32;
+        %type expr i32;
+        %type Num i32;
+        %type Name String;
+        %type Let String;
+
+        input ::= stmt* ;
+        stmt ::= Name Eq expr(e) Semi { e }
+        stmt ::= Let Name Eq expr(e) Semi { -e }
+        stmt ::= Skip Any Semi { 0 }
+        stmt ::= error Semi { -1 }
+        expr ::= Num;
+        expr ::= Name(n) { n.len() as i32 }
+        expr ::= expr(a) Plus expr(b) { a + b }
+        expr ::= expr(a) Times expr(b) { a * b }
+        expr ::= LParen expr RParen;
+    }
+
+    use parser::*;
+
+    fn parse(tokens: Vec<Token>) -> Result<Vec<i32>, ()> {
+        let mut p = Parser::new();
+        for tok in tokens {
+            p.parse(tok)?;
+        }
+        p.end_of_input()
+    }
+
+    #[test]
+    fn tables() -> Result<(), ()> {
+        use Token::*;
+        let name = |s: &str| Name(s.to_string());
+        let toks = vec![
+            //`let` is a name where a `Let` is not expected
+            name("x"), Eq, Let("let".to_string()), Plus, Num(2), Times, Num(3), Semi,
+            Let("let".to_string()), name("y"), Eq, LParen, Num(1), Plus, Num(2), RParen, Semi,
+            Skip, Plus, Semi,
+            Skip, Num(4), Semi,
+            Num(5), Eq, Semi,
+            name("z"), Eq, Num(7), Semi,
+        ];
+        assert_eq!(parse(toks)?, [9, -3, 0, 0, -1, 7]);
+        assert!(parse(vec![name("x"), Eq, Num(1)]).is_err());
+        Ok(())
+    }
+}
+
+mod dense {
+    use pomelo::*;
+
+    pomelo! {
+        %tables dense;
+        %syntax_error { Ok(()) }
+        %fallback Name Let;
+        %wildcard Any;
+        %left Plus;
+        %left Times;
+        %type input Vec<i32>;
+        %type stmt i32;
+        %type expr i32;
+        %type Num i32;
+        %type Name String;
+        %type Let String;
+
+        input ::= stmt* ;
+        stmt ::= Name Eq expr(e) Semi { e }
+        stmt ::= Let Name Eq expr(e) Semi { -e }
+        stmt ::= Skip Any Semi { 0 }
+        stmt ::= error Semi { -1 }
+        expr ::= Num;
+        expr ::= Name(n) { n.len() as i32 }
+        expr ::= expr(a) Plus expr(b) { a + b }
+        expr ::= expr(a) Times expr(b) { a * b }
+        expr ::= LParen expr RParen;
+    }
+
+    use parser::*;
+
+    fn parse(tokens: Vec<Token>) -> Result<Vec<i32>, ()> {
+        let mut p = Parser::new();
+        for tok in tokens {
+            p.parse(tok)?;
+        }
+        p.end_of_input()
+    }
+
+    #[test]
+    fn tables() -> Result<(), ()> {
+        use Token::*;
+        let name = |s: &str| Name(s.to_string());
+        let toks = vec![
+            //`let` is a name where a `Let` is not expected
+            name("x"), Eq, Let("let".to_string()), Plus, Num(2), Times, Num(3), Semi,
+            Let("let".to_string()), name("y"), Eq, LParen, Num(1), Plus, Num(2), RParen, Semi,
+            Skip, Plus, Semi,
+            Skip, Num(4), Semi,
+            Num(5), Eq, Semi,
+            name("z"), Eq, Num(7), Semi,
+        ];
+        assert_eq!(parse(toks)?, [9, -3, 0, 0, -1, 7]);
+        assert!(parse(vec![name("x"), Eq, Num(1)]).is_err());
+        Ok(())
+    }
+}
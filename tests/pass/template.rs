@@ -0,0 +1,67 @@
+use pomelo::*;
+
+pomelo! {
+    %type input (Vec<i32>, Option<String>, Vec<(String, i32)>);
+    %type Num i32;
+    %type Ident String;
+
+    input ::= delimited(LBracket, list(Num), RBracket)(a) option(Ident)(b) Semicolon list(pair(Ident, Num))(c) {
+        (a, b, c)
+    }
+
+    %type list(X) Vec<X>;
+    list(X) ::= X(a) { vec![a] }
+    list(X) ::= list(X)(mut l) Comma X(a) { l.push(a); l }
+
+    %type option(X) Option<X>;
+    option(X) ::= { None }
+    option(X) ::= X(x) { Some(x) }
+
+    %type pair(K, V) (K, V);
+    pair(K, V) ::= K(k) Colon V(v) { (k, v) }
+
+    %type delimited(L, X, R) X;
+    delimited(L, X, R) ::= L X(x) R { x }
+}
+
+use parser::*;
+
+type Output = (Vec<i32>, Option<String>, Vec<(String, i32)>);
+
+fn parse(tokens: Vec<Token>) -> Result<Output, ()> {
+    let mut parse = Parser::new();
+    for tok in tokens {
+        parse.parse(tok)?;
+    }
+    parse.end_of_input()
+}
+
+fn id(s: &str) -> Token {
+    Token::Ident(s.to_string())
+}
+
+#[test]
+fn instances() -> Result<(), ()> {
+    use Token::*;
+    let res = parse(vec![
+        LBracket, Num(1), Comma, Num(2), RBracket,
+        id("a"),
+        Semicolon,
+        id("x"), Colon, Num(3), Comma, id("y"), Colon, Num(4),
+    ])?;
+    assert_eq!(
+        res,
+        (
+            vec![1, 2],
+            Some(String::from("a")),
+            vec![(String::from("x"), 3), (String::from("y"), 4)]
+        )
+    );
+    let res = parse(vec![
+        LBracket, Num(1), RBracket,
+        Semicolon,
+        id("x"), Colon, Num(3),
+    ])?;
+    assert_eq!(res, (vec![1], None, vec![(String::from("x"), 3)]));
+    Ok(())
+}